@@ -0,0 +1,357 @@
+//! Authenticated `/admin` API for inspecting and controlling the running router.
+//!
+//! Requests need one of the `admin_api_keys`; the inference `api_keys` are not
+//! accepted. Without admin keys the endpoints answer 404.
+
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::Json,
+    routing::{get, post},
+};
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+use crate::{
+    auth::Principal,
+    balancer::ProviderMode,
+    config::Provider,
+    proxy::extract_api_key,
+    registry::ProviderRefreshStatus,
+    routes::{AppError, AppState, Readiness, Snapshot},
+    token::TokenStatus,
+};
+
+/// Routes of the admin API
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/providers", get(list_providers))
+        .route("/admin/providers/{name}/{action}", post(control_provider))
+        .route("/admin/models", get(list_models))
+        .route("/admin/refresh", post(refresh))
+        .route("/admin/tokens", get(list_tokens))
+        .route("/admin/ready", get(readiness))
+}
+
+/// Check the admin key and return the caller for logging
+fn authorize(snapshot: &Snapshot, headers: &HeaderMap) -> Result<Principal, AppError> {
+    let admin_api_keys = &snapshot.config.admin_api_keys;
+    if admin_api_keys.is_empty() {
+        return Err(AppError::AdminDisabled);
+    }
+    let key = extract_api_key(headers).ok_or(AppError::MissingApiKey)?;
+    if !admin_api_keys.contains(&key) {
+        return Err(AppError::InvalidApiKey);
+    }
+    Ok(Principal::from_api_key(&key))
+}
+
+/// Overall state of a provider, from its runtime mode, last deployment
+/// refresh and token
+fn health(
+    provider: &Provider,
+    mode: ProviderMode,
+    refresh: Option<&ProviderRefreshStatus>,
+    token: &TokenStatus,
+) -> &'static str {
+    if !provider.enabled || mode != ProviderMode::Enabled {
+        return "disabled";
+    }
+    match refresh {
+        None => "unknown",
+        Some(refresh) if refresh.last_success.is_none() => "failing",
+        Some(refresh) if refresh.stale || refresh.last_error.is_some() => "stale",
+        Some(_) if token.consecutive_failures > 0 && !token.valid => "failing",
+        Some(_) => "healthy",
+    }
+}
+
+fn timestamp(time: Option<SystemTime>) -> Option<DateTime<Utc>> {
+    time.map(DateTime::<Utc>::from)
+}
+
+fn token_json(token: &TokenStatus) -> Value {
+    json!({
+        "expires_at": token.expires_at,
+        "valid": token.valid,
+        "consecutive_failures": token.consecutive_failures,
+        "last_error": token.last_error,
+    })
+}
+
+async fn provider_json(state: &AppState, snapshot: &Snapshot, provider: &Provider) -> Value {
+    let status = snapshot.model_registry.provider_status().await;
+    let refresh = status.get(&provider.name);
+    let mode = snapshot.load_balancer.provider_mode(&provider.name);
+    let token = state.token_manager.token_status(provider);
+    json!({
+        "name": provider.name,
+        "enabled": provider.enabled,
+        "mode": mode,
+        "health": health(provider, mode, refresh, &token),
+        "in_flight": snapshot.load_balancer.in_flight(&provider.name),
+        "resource_groups": provider.resource_group_patterns(),
+        "refresh": refresh.map(|refresh| json!({
+            "last_success": timestamp(refresh.last_success),
+            "last_attempt": timestamp(refresh.last_attempt),
+            "last_error": refresh.last_error,
+            "stale": refresh.stale,
+        })),
+        "token": token_json(&token),
+    })
+}
+
+async fn providers_json(state: &AppState, snapshot: &Snapshot) -> Value {
+    let mut providers = Vec::new();
+    for provider in &snapshot.config.providers {
+        providers.push(provider_json(state, snapshot, provider).await);
+    }
+    json!({ "providers": providers })
+}
+
+pub async fn list_providers(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let snapshot = state.snapshot();
+    authorize(&snapshot, &headers)?;
+    Ok(Json(providers_json(&state, &snapshot).await))
+}
+
+/// Enable, disable or drain a provider until the next restart
+pub async fn control_provider(
+    State(state): State<AppState>,
+    Path((name, action)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let snapshot = state.snapshot();
+    let principal = authorize(&snapshot, &headers)?;
+
+    let mode = match action.as_str() {
+        "enable" => ProviderMode::Enabled,
+        "disable" => ProviderMode::Disabled,
+        "drain" => ProviderMode::Draining,
+        _ => {
+            return Err(AppError::BadRequest(format!(
+                "Unknown action '{action}'. Expected enable, disable or drain"
+            )));
+        }
+    };
+    let provider = snapshot
+        .config
+        .providers
+        .iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| AppError::ProviderNotFound(name.clone()))?;
+    if !provider.enabled {
+        return Err(AppError::BadRequest(format!(
+            "Provider '{name}' is disabled in the configuration"
+        )));
+    }
+
+    snapshot.load_balancer.set_provider_mode(&name, mode);
+    tracing::info!("Admin {}: provider '{}' set to {:?}", principal, name, mode);
+    Ok(Json(provider_json(&state, &snapshot, provider).await))
+}
+
+/// Resolved models with their deployments, grouped by provider
+pub async fn list_models(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let snapshot = state.snapshot();
+    authorize(&snapshot, &headers)?;
+
+    let mut models: BTreeMap<String, BTreeMap<String, Vec<Value>>> = BTreeMap::new();
+    for (model, deployments) in snapshot.model_registry.resolved_models().await {
+        let providers = models.entry(model).or_default();
+        for deployment in deployments {
+            providers
+                .entry(deployment.provider_name)
+                .or_default()
+                .push(json!({
+                    "deployment_id": deployment.deployment_id,
+                    "resource_group": deployment.resource_group,
+                }));
+        }
+    }
+    Ok(Json(json!({ "models": models })))
+}
+
+/// Refresh the deployments of all providers and return their state
+pub async fn refresh(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let snapshot = state.snapshot();
+    let principal = authorize(&snapshot, &headers)?;
+
+    tracing::info!("Admin {}: refreshing deployments", principal);
+    snapshot.model_registry.refresh_deployments().await?;
+    Ok(Json(providers_json(&state, &snapshot).await))
+}
+
+/// Token cache state per provider; the tokens themselves are never returned
+pub async fn list_tokens(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let snapshot = state.snapshot();
+    authorize(&snapshot, &headers)?;
+
+    let tokens: BTreeMap<&str, Value> = snapshot
+        .config
+        .providers
+        .iter()
+        .map(|provider| {
+            let token = state.token_manager.token_status(provider);
+            (provider.name.as_str(), token_json(&token))
+        })
+        .collect();
+    Ok(Json(json!({ "tokens": tokens })))
+}
+
+/// `/ready`, including the last refresh error of each provider
+pub async fn readiness(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<Value>), AppError> {
+    let snapshot = state.snapshot();
+    authorize(&snapshot, &headers)?;
+
+    let readiness = Readiness::check(&state, &snapshot).await;
+    Ok((readiness.status(), Json(readiness.to_json(true))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::Authenticator, balancer::LoadBalancer, config::Config, registry::ModelRegistry,
+        token::TokenManager,
+    };
+
+    fn app_state(admin_api_keys: &str) -> AppState {
+        // Unroutable endpoints: nothing is fetched by these tests
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+api_keys: [inference-key]
+admin_api_keys: {admin_api_keys}
+models:
+  - name: gpt-4o
+providers:
+  - name: eu10
+    uaa_token_url: http://127.0.0.1:1/oauth/token
+    uaa_client_id: eu10-client
+    uaa_client_secret: eu10-secret
+    genai_api_url: http://127.0.0.1:1
+"#
+        ))
+        .unwrap();
+        let token_manager = TokenManager::new();
+        let snapshot = Snapshot {
+            authenticator: Authenticator::from_config(&config),
+            load_balancer: LoadBalancer::new(
+                config.providers.clone(),
+                config.load_balancing.clone(),
+            ),
+            model_registry: ModelRegistry::from_config(&config, token_manager.clone()),
+            config,
+        };
+        AppState::new(snapshot, token_manager)
+    }
+
+    fn with_key(key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", key.parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_admin_key_required() {
+        let state = app_state("[]");
+        let result = list_providers(State(state), with_key("inference-key")).await;
+        assert!(matches!(result, Err(AppError::AdminDisabled)));
+
+        let state = app_state("[admin-key]");
+        let result = list_providers(State(state.clone()), HeaderMap::new()).await;
+        assert!(matches!(result, Err(AppError::MissingApiKey)));
+        let result = list_providers(State(state.clone()), with_key("inference-key")).await;
+        assert!(matches!(result, Err(AppError::InvalidApiKey)));
+        assert!(
+            list_providers(State(state), with_key("admin-key"))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_control_provider() {
+        let state = app_state("[admin-key]");
+        let control = |name: &str, action: &str| {
+            control_provider(
+                State(state.clone()),
+                Path((name.to_string(), action.to_string())),
+                with_key("admin-key"),
+            )
+        };
+
+        let Json(provider) = control("eu10", "drain").await.unwrap();
+        // Nothing in flight, so draining completes right away
+        assert_eq!(provider["mode"], "disabled");
+        assert_eq!(provider["health"], "disabled");
+        assert!(
+            state
+                .snapshot()
+                .load_balancer
+                .get_ordered_providers()
+                .is_empty()
+        );
+
+        let Json(provider) = control("eu10", "enable").await.unwrap();
+        assert_eq!(provider["mode"], "enabled");
+        assert_eq!(provider["health"], "unknown");
+        assert_eq!(
+            state.snapshot().load_balancer.get_ordered_providers().len(),
+            1
+        );
+
+        assert!(matches!(
+            control("us10", "disable").await,
+            Err(AppError::ProviderNotFound(_))
+        ));
+        assert!(matches!(
+            control("eu10", "pause").await,
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tokens_without_secrets() {
+        let state = app_state("[admin-key]");
+        let Json(tokens) = list_tokens(State(state), with_key("admin-key"))
+            .await
+            .unwrap();
+        assert_eq!(tokens["tokens"]["eu10"]["valid"], false);
+        assert!(!tokens.to_string().contains("eu10-secret"));
+    }
+
+    #[tokio::test]
+    async fn test_readiness_breakdown() {
+        let state = app_state("[admin-key]");
+        let result = readiness(State(state.clone()), HeaderMap::new()).await;
+        assert!(matches!(result, Err(AppError::MissingApiKey)));
+
+        let (status, Json(body)) = readiness(State(state), with_key("admin-key"))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["providers"][0]["name"], "eu10");
+        assert_eq!(body["providers"][0]["token_valid"], false);
+        assert!(body["providers"][0].get("last_error").is_some());
+    }
+}
@@ -0,0 +1,443 @@
+//! Model alias matching.
+//!
+//! An alias is an exact name, a glob where `*` matches any run of characters,
+//! or a regex starting with `^`. A `(?i)` prefix makes any of them
+//! case-insensitive. Aliases listed on a model resolve to that model; rewrites
+//! compute the model name from the captures, e.g. `claude-$1-4-5`.
+//!
+//! When several aliases match, the one with the most literal characters wins,
+//! then exact names over globs over regexes, then case-sensitive over
+//! case-insensitive. Remaining ties go to the alphabetically first target, so
+//! the result never depends on the order of the configuration.
+
+use anyhow::{Context, Result};
+use regex_automata::dfa::{Automaton, dense};
+use regex_automata::meta::Regex;
+use regex_automata::util::{primitives::StateID, syntax};
+use regex_automata::{Anchored, Input};
+use regex_syntax::hir::{Hir, HirKind};
+use std::cmp::Ordering;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use crate::config::{AliasRewrite, Model};
+
+const CASE_INSENSITIVE_PREFIX: &str = "(?i)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PatternKind {
+    Regex,
+    Glob,
+    Exact,
+}
+
+impl fmt::Display for PatternKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PatternKind::Regex => "regex",
+            PatternKind::Glob => "glob",
+            PatternKind::Exact => "exact",
+        })
+    }
+}
+
+/// A compiled alias pattern
+#[derive(Debug, Clone)]
+pub struct AliasPattern {
+    pub source: String,
+    pub kind: PatternKind,
+    pub case_insensitive: bool,
+    /// Characters every matching name must contain literally
+    pub literal_chars: usize,
+    /// The pattern as an anchored regex
+    expression: String,
+    regex: Regex,
+}
+
+impl AliasPattern {
+    pub fn parse(source: &str) -> Result<Self> {
+        let (pattern, case_insensitive) = match source.strip_prefix(CASE_INSENSITIVE_PREFIX) {
+            Some(pattern) => (pattern, true),
+            None => (source, false),
+        };
+        let (kind, regex) = if pattern.starts_with('^') {
+            (PatternKind::Regex, pattern.to_string())
+        } else if pattern.contains('*') {
+            let parts: Vec<String> = pattern.split('*').map(regex_syntax::escape).collect();
+            (PatternKind::Glob, format!("^{}$", parts.join("(.*)")))
+        } else {
+            (
+                PatternKind::Exact,
+                format!("^{}$", regex_syntax::escape(pattern)),
+            )
+        };
+
+        let hir = regex_syntax::parse(&regex)
+            .with_context(|| format!("Invalid alias pattern '{source}'"))?;
+        let compiled = Regex::builder()
+            .syntax(syntax::Config::new().case_insensitive(case_insensitive))
+            .build(&regex)
+            .with_context(|| format!("Invalid alias pattern '{source}'"))?;
+
+        Ok(Self {
+            source: source.to_string(),
+            kind,
+            case_insensitive,
+            literal_chars: literal_chars(&hir),
+            expression: regex,
+            regex: compiled,
+        })
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+
+    /// Expand `$1`, `${1}` or `$name` in `template` with the captures of `name`.
+    /// In the config file `${1}` is written `$${1}` to escape interpolation.
+    fn expand(&self, name: &str, template: &str) -> Option<String> {
+        let mut captures = self.regex.create_captures();
+        self.regex.captures(name, &mut captures);
+        captures
+            .is_match()
+            .then(|| captures.interpolate_string(name, template))
+    }
+
+    /// Ranking key; greater is more specific.
+    fn rank(&self) -> (usize, PatternKind, bool) {
+        (self.literal_chars, self.kind, !self.case_insensitive)
+    }
+
+    /// Whether `other` is equally specific, so that for names matching both
+    /// the target name decides.
+    pub fn ties_with(&self, other: &AliasPattern) -> bool {
+        self.rank() == other.rank()
+    }
+
+    /// Whether some name matches both patterns. Walks both automata in step
+    /// until they accept the same input; patterns too large to build a DFA
+    /// for are assumed not to overlap.
+    pub fn overlaps(&self, other: &AliasPattern) -> bool {
+        let (Some(a), Some(b)) = (self.dfa(), other.dfa()) else {
+            return false;
+        };
+        let start = |dfa: &dense::DFA<Vec<u32>>| {
+            dfa.start_state_forward(&Input::new("").anchored(Anchored::Yes))
+                .ok()
+        };
+        let (Some(start_a), Some(start_b)) = (start(&a), start(&b)) else {
+            return false;
+        };
+
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from([((start_a, false), (start_b, false))]);
+        while let Some(state @ (state_a, state_b)) = queue.pop_front() {
+            if !seen.insert(state) {
+                continue;
+            }
+            if accepts(&a, state_a) && accepts(&b, state_b) {
+                return true;
+            }
+            for byte in 0..=u8::MAX {
+                if let (Some(next_a), Some(next_b)) =
+                    (step(&a, state_a, byte), step(&b, state_b, byte))
+                {
+                    queue.push_back((next_a, next_b));
+                }
+            }
+        }
+        false
+    }
+
+    fn dfa(&self) -> Option<dense::DFA<Vec<u32>>> {
+        dense::Builder::new()
+            .syntax(syntax::Config::new().case_insensitive(self.case_insensitive))
+            .configure(dense::Config::new().dfa_size_limit(Some(DFA_SIZE_LIMIT)))
+            .build(&self.expression)
+            .ok()
+    }
+}
+
+/// Limit on the automata built to compare two patterns
+const DFA_SIZE_LIMIT: usize = 1 << 20;
+
+/// DFA state, and whether the input read so far already contains a match.
+/// Patterns without `$` match every name starting with a match.
+type WalkState = (StateID, bool);
+
+fn step(dfa: &dense::DFA<Vec<u32>>, (state, matched): WalkState, byte: u8) -> Option<WalkState> {
+    if matched {
+        return Some((state, true));
+    }
+    let next = dfa.next_state(state, byte);
+    if dfa.is_dead_state(next) {
+        None
+    } else {
+        // Matches are reported one byte late, so this one ended before `byte`
+        Some((next, dfa.is_match_state(next)))
+    }
+}
+
+fn accepts(dfa: &dense::DFA<Vec<u32>>, (state, matched): WalkState) -> bool {
+    matched || dfa.is_match_state(dfa.next_eoi_state(state))
+}
+
+/// Minimum number of literal characters in any string the expression matches.
+fn literal_chars(hir: &Hir) -> usize {
+    match hir.kind() {
+        HirKind::Literal(literal) => String::from_utf8_lossy(&literal.0).chars().count(),
+        HirKind::Capture(capture) => literal_chars(&capture.sub),
+        HirKind::Repetition(repetition) if repetition.min > 0 => literal_chars(&repetition.sub),
+        HirKind::Concat(hirs) => hirs.iter().map(literal_chars).sum(),
+        HirKind::Alternation(hirs) => hirs.iter().map(literal_chars).min().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// What an alias resolves to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AliasTarget {
+    /// A configured model
+    Model(String),
+    /// A model name built from the pattern's captures
+    Template(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct AliasRule {
+    pub pattern: AliasPattern,
+    pub target: AliasTarget,
+}
+
+impl fmt::Display for AliasRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target {
+            AliasTarget::Model(ref model) => {
+                write!(f, "alias '{}' of model '{model}'", self.pattern.source)
+            }
+            AliasTarget::Template(ref template) => {
+                write!(f, "rewrite '{}' to '{template}'", self.pattern.source)
+            }
+        }
+    }
+}
+
+/// An alias matching a requested name
+#[derive(Debug, Clone)]
+pub struct AliasMatch<'a> {
+    pub rule: &'a AliasRule,
+    /// The model name the requested name resolves to
+    pub target: String,
+}
+
+impl AliasMatch<'_> {
+    /// How specific the match is, e.g. "glob, 18 literal characters".
+    pub fn specificity(&self) -> String {
+        let pattern = &self.rule.pattern;
+        format!(
+            "{}, {} literal characters{}",
+            pattern.kind,
+            pattern.literal_chars,
+            if pattern.case_insensitive {
+                ", case-insensitive"
+            } else {
+                ""
+            }
+        )
+    }
+
+    /// Whether `other` is equally specific, leaving the target name to decide.
+    pub fn ties_with(&self, other: &AliasMatch) -> bool {
+        self.rule.pattern.ties_with(&other.rule.pattern)
+    }
+
+    fn cmp_specificity(&self, other: &AliasMatch) -> Ordering {
+        other
+            .rule
+            .pattern
+            .rank()
+            .cmp(&self.rule.pattern.rank())
+            .then_with(|| self.target.cmp(&other.target))
+            .then_with(|| self.rule.pattern.source.cmp(&other.rule.pattern.source))
+    }
+}
+
+/// Model aliases and rewrites from the configuration
+#[derive(Debug, Clone, Default)]
+pub struct AliasResolver {
+    rules: Vec<AliasRule>,
+}
+
+impl AliasResolver {
+    pub fn new(models: &[Model], rewrites: &[AliasRewrite]) -> Result<Self> {
+        let mut rules = Vec::new();
+        for model in models {
+            for alias in &model.aliases {
+                rules.push(AliasRule {
+                    pattern: AliasPattern::parse(alias)
+                        .with_context(|| format!("Model '{}'", model.name))?,
+                    target: AliasTarget::Model(model.name.clone()),
+                });
+            }
+        }
+        for rewrite in rewrites {
+            rules.push(AliasRule {
+                pattern: AliasPattern::parse(&rewrite.pattern).context("alias_rewrites")?,
+                target: AliasTarget::Template(rewrite.target.clone()),
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    /// All aliases matching `name`, most specific first.
+    pub fn matches(&self, name: &str) -> Vec<AliasMatch<'_>> {
+        let mut matches: Vec<AliasMatch> = self
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                let target = match rule.target {
+                    AliasTarget::Model(ref model) => {
+                        rule.pattern.is_match(name).then(|| model.clone())
+                    }
+                    AliasTarget::Template(ref template) => rule.pattern.expand(name, template),
+                }?;
+                Some(AliasMatch { rule, target })
+            })
+            .collect();
+        matches.sort_by(AliasMatch::cmp_specificity);
+        matches
+    }
+
+    /// The most specific alias matching `name`.
+    pub fn resolve(&self, name: &str) -> Option<AliasMatch<'_>> {
+        self.matches(name).into_iter().next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(name: &str, aliases: &[&str]) -> Model {
+        Model {
+            name: name.to_string(),
+            aicore_model_name: None,
+            aicore_model_version: None,
+            configuration_name: None,
+            deployment_ids: Default::default(),
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+            timeouts: Default::default(),
+            request: Default::default(),
+        }
+    }
+
+    fn rewrite(pattern: &str, target: &str) -> AliasRewrite {
+        AliasRewrite {
+            pattern: pattern.to_string(),
+            target: target.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_pattern_kinds() {
+        let exact = AliasPattern::parse("claude-4-sonnet").unwrap();
+        assert_eq!(exact.kind, PatternKind::Exact);
+        assert_eq!(exact.literal_chars, 15);
+        assert!(exact.is_match("claude-4-sonnet"));
+        assert!(!exact.is_match("claude-4-sonnet-x"));
+
+        let glob = AliasPattern::parse("claude-*-4-5").unwrap();
+        assert_eq!(glob.kind, PatternKind::Glob);
+        assert_eq!(glob.literal_chars, 11);
+        assert!(glob.is_match("claude-sonnet-4-5"));
+        assert!(!glob.is_match("claude-sonnet-4-5-20250929"));
+
+        let regex = AliasPattern::parse(r"^claude-(\w+)-4-5-\d{8}$").unwrap();
+        assert_eq!(regex.kind, PatternKind::Regex);
+        assert_eq!(regex.literal_chars, 12);
+        assert!(regex.is_match("claude-opus-4-5-20251101"));
+
+        let insensitive = AliasPattern::parse("(?i)GPT-4o*").unwrap();
+        assert!(insensitive.case_insensitive);
+        assert!(insensitive.is_match("gpt-4o-mini"));
+
+        assert!(AliasPattern::parse("^claude-(").is_err());
+    }
+
+    #[test]
+    fn test_rewrite_with_captures() {
+        let resolver = AliasResolver::new(
+            &[],
+            &[rewrite(r"^claude-(\w+)-4-5-\d{8}$", "claude-$1-4-5")],
+        )
+        .unwrap();
+        let matched = resolver.resolve("claude-haiku-4-5-20251001").unwrap();
+        assert_eq!(matched.target, "claude-haiku-4-5");
+        assert!(resolver.resolve("claude-haiku-4-5").is_none());
+
+        // Glob wildcards capture too
+        let resolver =
+            AliasResolver::new(&[], &[rewrite("(?i)Sonnet-*", "claude-sonnet-$1")]).unwrap();
+        assert_eq!(
+            resolver.resolve("sonnet-4-5").unwrap().target,
+            "claude-sonnet-4-5"
+        );
+    }
+
+    #[test]
+    fn test_most_specific_wins_regardless_of_order() {
+        let models = [
+            model("claude-general", &["claude-*"]),
+            model("claude-sonnet-4-5", &["claude-sonnet-4-5-*"]),
+        ];
+        let reversed = [models[1].clone(), models[0].clone()];
+        for models in [&models[..], &reversed[..]] {
+            let resolver = AliasResolver::new(models, &[]).unwrap();
+            let matched = resolver.resolve("claude-sonnet-4-5-20250929").unwrap();
+            assert_eq!(matched.target, "claude-sonnet-4-5");
+            assert_eq!(resolver.matches("claude-sonnet-4-5-20250929").len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_ties_are_deterministic() {
+        let models = [
+            model("b-model", &["claude-*"]),
+            model("a-model", &["claude-*"]),
+        ];
+        let reversed = [models[1].clone(), models[0].clone()];
+        for models in [&models[..], &reversed[..]] {
+            let resolver = AliasResolver::new(models, &[]).unwrap();
+            let matches = resolver.matches("claude-opus");
+            assert_eq!(matches[0].target, "a-model");
+            assert!(matches[0].ties_with(&matches[1]));
+        }
+
+        // Same literal characters: case-sensitive beats case-insensitive
+        let models = [
+            model("insensitive", &["(?i)claude-*"]),
+            model("sensitive", &["claude-*"]),
+        ];
+        let resolver = AliasResolver::new(&models, &[]).unwrap();
+        assert_eq!(resolver.resolve("claude-opus").unwrap().target, "sensitive");
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let overlaps = |a: &str, b: &str| {
+            let (a, b) = (
+                AliasPattern::parse(a).unwrap(),
+                AliasPattern::parse(b).unwrap(),
+            );
+            assert_eq!(a.overlaps(&b), b.overlaps(&a));
+            a.overlaps(&b)
+        };
+        // Both match claude-4-4
+        assert!(overlaps("claude-*-4", "claude-4-*"));
+        assert!(overlaps("(?i)CLAUDE-*", "claude-opus"));
+        assert!(overlaps(r"^claude-\w+-4-5", "claude-opus-4-5-20251101"));
+        assert!(!overlaps("claude-*", "gpt-*"));
+        assert!(!overlaps(r"^claude-\d+$", "claude-opus"));
+        assert!(!overlaps("gpt-4o-*", "gpt-4o"));
+    }
+}
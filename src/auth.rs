@@ -0,0 +1,540 @@
+//! Inbound request authentication with static API keys or JWT bearer tokens.
+
+use anyhow::{Context, Result};
+use jsonwebtoken::{DecodingKey, Validation, decode, decode_header, jwk::JwkSet};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+
+use crate::config::{Config, HttpClientConfig, JwtAuthConfig, TimeoutConfig};
+use crate::errors::AuthError;
+
+/// Minimum time between two JWKS reloads triggered by an unknown `kid` or a failed reload
+const JWKS_MIN_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long past `jwks_cache_secs` cached keys keep being used while reloads fail
+const JWKS_STALE_GRACE: Duration = Duration::from_secs(3600);
+
+/// How a principal was authenticated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    ApiKey,
+    Jwt,
+    /// Verified TLS client certificate (mTLS listeners)
+    ClientCertificate,
+}
+
+/// Authenticated caller identity, shared by all authentication methods.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// Stable identity used for logging and rate limiting
+    pub subject: String,
+    /// Scopes granted to the caller (empty for API keys)
+    pub scopes: Vec<String>,
+    pub method: AuthMethod,
+}
+
+impl Principal {
+    /// Principal for a static API key. The key itself is never exposed;
+    /// the subject is derived from a SHA-256 fingerprint.
+    pub fn from_api_key(api_key: &str) -> Self {
+        let digest = Sha256::digest(api_key.as_bytes());
+        let fingerprint: String = digest[..6].iter().map(|b| format!("{b:02x}")).collect();
+        Self {
+            subject: format!("api-key:{fingerprint}"),
+            scopes: Vec::new(),
+            method: AuthMethod::ApiKey,
+        }
+    }
+
+    /// Principal for a client certificate verified during the TLS handshake.
+    pub fn from_client_certificate(identity: &str) -> Self {
+        Self {
+            subject: format!("cert:{identity}"),
+            scopes: Vec::new(),
+            method: AuthMethod::ClientCertificate,
+        }
+    }
+}
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.subject)
+    }
+}
+
+/// Check whether a credential has the shape of a compact JWS (`header.payload.signature`).
+pub fn looks_like_jwt(credential: &str) -> bool {
+    let parts: Vec<&str> = credential.split('.').collect();
+    parts.len() == 3
+        && parts.iter().all(|p| {
+            !p.is_empty()
+                && p.bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        })
+}
+
+#[derive(Debug)]
+struct CachedJwks {
+    keys: JwkSet,
+    loaded_at: Instant,
+    /// Last reload attempt, successful or not
+    checked_at: Instant,
+}
+
+/// Validates JWT bearer tokens against a cached JWKS.
+#[derive(Debug, Clone)]
+pub struct JwtValidator {
+    config: JwtAuthConfig,
+    jwks: Arc<RwLock<Option<CachedJwks>>>,
+    /// Serializes reloads so concurrent requests share one JWKS fetch
+    reload: Arc<Mutex<()>>,
+    /// Outbound HTTP settings for fetching `jwks_url`
+    http: HttpClientConfig,
+    timeouts: TimeoutConfig,
+}
+
+impl JwtValidator {
+    pub fn new(config: JwtAuthConfig, http: HttpClientConfig, timeouts: TimeoutConfig) -> Self {
+        Self {
+            config,
+            jwks: Arc::new(RwLock::new(None)),
+            reload: Arc::new(Mutex::new(())),
+            http,
+            timeouts,
+        }
+    }
+
+    /// Validate signature, issuer, audience and expiry, and map the claims to a principal.
+    pub async fn validate(&self, token: &str) -> Result<Principal, AuthError> {
+        let header = decode_header(token).map_err(|e| AuthError::InvalidToken {
+            details: e.to_string(),
+        })?;
+
+        let jwks = self.jwks_for(header.kid.as_deref()).await?;
+        let jwk = match header.kid.as_deref() {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| AuthError::InvalidToken {
+            details: "no matching signing key in JWKS".to_string(),
+        })?;
+
+        if let Some(key_alg) = jwk.common.key_algorithm
+            && key_alg.to_string() != format!("{:?}", header.alg)
+        {
+            return Err(AuthError::InvalidToken {
+                details: format!(
+                    "token algorithm {:?} does not match key algorithm {key_alg}",
+                    header.alg
+                ),
+            });
+        }
+
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| AuthError::InvalidToken {
+            details: format!("unusable signing key: {e}"),
+        })?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&self.config.audiences);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        validation.leeway = self.config.leeway_secs;
+
+        let claims = decode::<Value>(token, &key, &validation)
+            .map_err(|e| AuthError::InvalidToken {
+                details: e.to_string(),
+            })?
+            .claims;
+
+        let subject = claims
+            .get(&self.config.subject_claim)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| AuthError::InvalidToken {
+                details: format!("missing '{}' claim", self.config.subject_claim),
+            })?;
+
+        let scopes = match claims.get(&self.config.scopes_claim) {
+            Some(Value::String(s)) => s.split_whitespace().map(str::to_string).collect(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(Principal {
+            subject: format!("jwt:{subject}"),
+            scopes,
+            method: AuthMethod::Jwt,
+        })
+    }
+
+    /// Return the cached JWKS, reloading it when expired or when `kid` is unknown
+    /// (signing key rotation). Reloads are rate limited and run one at a time; when
+    /// one fails, the cached keys stay in use for up to [`JWKS_STALE_GRACE`].
+    async fn jwks_for(&self, kid: Option<&str>) -> Result<JwkSet, AuthError> {
+        if let Some(keys) = self.cached_jwks(kid).await {
+            return Ok(keys);
+        }
+
+        let waiting_since = Instant::now();
+        let _reload = self.reload.lock().await;
+        // Another request may have reloaded the keys while this one waited
+        if let Some(cached) = self.jwks.read().await.as_ref()
+            && cached.checked_at >= waiting_since
+        {
+            return Ok(cached.keys.clone());
+        }
+
+        let loaded = self.load_jwks().await;
+        let ttl = Duration::from_secs(self.config.jwks_cache_secs);
+        let mut cached = self.jwks.write().await;
+        match loaded {
+            Ok(keys) => {
+                let now = Instant::now();
+                *cached = Some(CachedJwks {
+                    keys: keys.clone(),
+                    loaded_at: now,
+                    checked_at: now,
+                });
+                Ok(keys)
+            }
+            Err(e) => match cached.as_mut() {
+                Some(cached) if cached.loaded_at.elapsed() < ttl + JWKS_STALE_GRACE => {
+                    tracing::warn!(
+                        "Failed to reload JWKS, keeping keys loaded {}s ago: {:#}",
+                        cached.loaded_at.elapsed().as_secs(),
+                        e
+                    );
+                    cached.checked_at = Instant::now();
+                    Ok(cached.keys.clone())
+                }
+                _ => Err(e.into()),
+            },
+        }
+    }
+
+    /// Cached keys, if they can be used for `kid` without a reload.
+    async fn cached_jwks(&self, kid: Option<&str>) -> Option<JwkSet> {
+        let ttl = Duration::from_secs(self.config.jwks_cache_secs);
+        let cached = self.jwks.read().await;
+        let cached = cached.as_ref()?;
+        let age = cached.loaded_at.elapsed();
+        let kid_known = kid.is_none_or(|k| cached.keys.find(k).is_some());
+        let fresh = age < ttl && kid_known;
+        // A failed reload extends the keys' lifetime by the grace period
+        let lifetime = if cached.checked_at > cached.loaded_at {
+            ttl + JWKS_STALE_GRACE
+        } else {
+            ttl
+        };
+        let recently_checked =
+            cached.checked_at.elapsed() < JWKS_MIN_RELOAD_INTERVAL && age < lifetime;
+        (fresh || recently_checked).then(|| cached.keys.clone())
+    }
+
+    async fn load_jwks(&self) -> Result<JwkSet> {
+        if let Some(ref path) = self.config.jwks_file {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Failed to read JWKS file: {path}"))?;
+            return serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse JWKS file: {path}"));
+        }
+
+        let url = self
+            .config
+            .jwks_url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("jwt_auth has neither jwks_file nor jwks_url"))?;
+
+        let timeouts = self.timeouts.resolve();
+        let request = crate::http::client(&self.http, timeouts.connect)?
+            .get(url)
+            .timeout(timeouts.total);
+        let response = crate::http::send(request, timeouts.first_byte)
+            .await
+            .context("Failed to request JWKS")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "JWKS request to {} failed with status {}",
+                url,
+                response.status()
+            ));
+        }
+
+        let keys = response
+            .json::<JwkSet>()
+            .await
+            .context("Failed to parse JWKS response")?;
+        tracing::debug!("Loaded {} signing keys from {}", keys.keys.len(), url);
+        Ok(keys)
+    }
+}
+
+/// Authenticates inbound credentials as either a static API key or a JWT.
+#[derive(Debug, Clone)]
+pub struct Authenticator {
+    api_keys: Arc<HashSet<String>>,
+    jwt: Option<JwtValidator>,
+}
+
+impl Authenticator {
+    pub fn new(api_keys: Vec<String>, jwt_auth: Option<JwtAuthConfig>) -> Self {
+        Self {
+            api_keys: Arc::new(api_keys.into_iter().collect()),
+            jwt: jwt_auth.map(|jwt| {
+                JwtValidator::new(jwt, HttpClientConfig::default(), TimeoutConfig::default())
+            }),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            api_keys: Arc::new(config.api_keys.iter().cloned().collect()),
+            jwt: config
+                .jwt_auth
+                .clone()
+                .map(|jwt| JwtValidator::new(jwt, config.http.clone(), config.timeouts.clone())),
+        }
+    }
+
+    /// Whether JWT bearer tokens are accepted
+    pub fn jwt_enabled(&self) -> bool {
+        self.jwt.is_some()
+    }
+
+    /// Authenticate a credential taken from the request headers.
+    /// Static keys are matched first, so a configured key is never parsed as a JWT.
+    pub async fn authenticate(&self, credential: &str) -> Result<Principal, AuthError> {
+        if self.api_keys.contains(credential) {
+            return Ok(Principal::from_api_key(credential));
+        }
+
+        match &self.jwt {
+            Some(validator) if looks_like_jwt(credential) => validator.validate(credential).await,
+            _ => Err(AuthError::InvalidApiKey),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    const SECRET: &[u8] = b"test-signing-secret-with-enough-entropy";
+    // base64url of SECRET
+    const SECRET_B64: &str = "dGVzdC1zaWduaW5nLXNlY3JldC13aXRoLWVub3VnaC1lbnRyb3B5";
+
+    fn jwt_config(dir: &TempDir) -> JwtAuthConfig {
+        let jwks_path = dir.path().join("jwks.json");
+        let jwks = json!({
+            "keys": [{"kty": "oct", "kid": "k1", "alg": "HS256", "k": SECRET_B64}]
+        });
+        std::fs::write(&jwks_path, jwks.to_string()).unwrap();
+
+        JwtAuthConfig {
+            issuer: "https://issuer.example.com".to_string(),
+            audiences: vec!["acr".to_string()],
+            jwks_file: Some(jwks_path.to_string_lossy().to_string()),
+            jwks_url: None,
+            jwks_cache_secs: 3600,
+            subject_claim: "sub".to_string(),
+            scopes_claim: "scope".to_string(),
+            leeway_secs: 0,
+        }
+    }
+
+    fn sign(claims: Value, kid: Option<&str>) -> String {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = kid.map(str::to_string);
+        encode(&header, &claims, &EncodingKey::from_secret(SECRET)).unwrap()
+    }
+
+    fn claims(exp_offset: i64) -> Value {
+        json!({
+            "iss": "https://issuer.example.com",
+            "aud": "acr",
+            "sub": "svc-billing",
+            "scope": "models:read models:invoke",
+            "exp": chrono::Utc::now().timestamp() + exp_offset,
+        })
+    }
+
+    #[test]
+    fn test_looks_like_jwt() {
+        assert!(looks_like_jwt("eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiJ4In0.c2ln"));
+        assert!(!looks_like_jwt("plain-api-key"));
+        assert!(!looks_like_jwt("a.b"));
+        assert!(!looks_like_jwt("a..c"));
+        assert!(!looks_like_jwt("a.b.c=d"));
+    }
+
+    #[tokio::test]
+    async fn test_static_api_key() {
+        let auth = Authenticator::new(vec!["key-one".to_string()], None);
+
+        let principal = auth.authenticate("key-one").await.unwrap();
+        assert_eq!(principal.method, AuthMethod::ApiKey);
+        assert!(principal.subject.starts_with("api-key:"));
+        assert!(!principal.subject.contains("key-one"));
+
+        assert!(matches!(
+            auth.authenticate("key-two").await,
+            Err(AuthError::InvalidApiKey)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_jwt_rejected_when_not_configured() {
+        let auth = Authenticator::new(vec!["key-one".to_string()], None);
+        let token = sign(claims(300), Some("k1"));
+
+        assert!(matches!(
+            auth.authenticate(&token).await,
+            Err(AuthError::InvalidApiKey)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_valid_jwt() {
+        let dir = TempDir::new().unwrap();
+        let auth = Authenticator::new(vec![], Some(jwt_config(&dir)));
+        let token = sign(claims(300), Some("k1"));
+
+        let principal = auth.authenticate(&token).await.unwrap();
+        assert_eq!(principal.method, AuthMethod::Jwt);
+        assert_eq!(principal.subject, "jwt:svc-billing");
+        assert_eq!(principal.scopes, vec!["models:read", "models:invoke"]);
+    }
+
+    #[tokio::test]
+    async fn test_jwt_claim_validation() {
+        let dir = TempDir::new().unwrap();
+        let auth = Authenticator::new(vec![], Some(jwt_config(&dir)));
+
+        let expired = sign(claims(-300), Some("k1"));
+        assert!(matches!(
+            auth.authenticate(&expired).await,
+            Err(AuthError::InvalidToken { .. })
+        ));
+
+        let mut wrong_aud = claims(300);
+        wrong_aud["aud"] = json!("someone-else");
+        assert!(matches!(
+            auth.authenticate(&sign(wrong_aud, Some("k1"))).await,
+            Err(AuthError::InvalidToken { .. })
+        ));
+
+        let mut wrong_iss = claims(300);
+        wrong_iss["iss"] = json!("https://evil.example.com");
+        assert!(matches!(
+            auth.authenticate(&sign(wrong_iss, Some("k1"))).await,
+            Err(AuthError::InvalidToken { .. })
+        ));
+
+        let unknown_kid = sign(claims(300), Some("k2"));
+        assert!(matches!(
+            auth.authenticate(&unknown_kid).await,
+            Err(AuthError::InvalidToken { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_jwt_bad_signature() {
+        let dir = TempDir::new().unwrap();
+        let auth = Authenticator::new(vec![], Some(jwt_config(&dir)));
+
+        let forged = encode(
+            &Header {
+                kid: Some("k1".to_string()),
+                ..Header::new(Algorithm::HS256)
+            },
+            &claims(300),
+            &EncodingKey::from_secret(b"another-secret"),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            auth.authenticate(&forged).await,
+            Err(AuthError::InvalidToken { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_array_scopes_claim() {
+        let dir = TempDir::new().unwrap();
+        let auth = Authenticator::new(vec![], Some(jwt_config(&dir)));
+
+        let mut array_scopes = claims(300);
+        array_scopes["scope"] = json!(["a", "b"]);
+        let principal = auth
+            .authenticate(&sign(array_scopes, Some("k1")))
+            .await
+            .unwrap();
+        assert_eq!(principal.scopes, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_cached_jwks_survive_failed_reload() {
+        use axum::{Json, Router, http::StatusCode, response::IntoResponse, routing::get};
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+        let failing = Arc::new(AtomicBool::new(false));
+        let hits = Arc::new(AtomicUsize::new(0));
+        let app = Router::new().route(
+            "/jwks",
+            get({
+                let failing = failing.clone();
+                let hits = hits.clone();
+                move || async move {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    if failing.load(Ordering::SeqCst) {
+                        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+                    }
+                    Json(json!({
+                        "keys": [{"kty": "oct", "kid": "k1", "alg": "HS256", "k": SECRET_B64}]
+                    }))
+                    .into_response()
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let dir = TempDir::new().unwrap();
+        let config = JwtAuthConfig {
+            jwks_file: None,
+            jwks_url: Some(format!("http://{addr}/jwks")),
+            jwks_cache_secs: 0,
+            ..jwt_config(&dir)
+        };
+        let auth = Authenticator::new(vec![], Some(config));
+        let token = sign(claims(300), Some("k1"));
+
+        // Concurrent requests share a single fetch
+        let results = futures::future::join_all((0..5).map(|_| auth.authenticate(&token))).await;
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // The expired keys are still used when the reload fails
+        failing.store(true, Ordering::SeqCst);
+        assert!(auth.authenticate(&token).await.is_ok());
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        // and the failing endpoint is not retried on every request
+        assert!(auth.authenticate(&token).await.is_ok());
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+}
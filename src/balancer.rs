@@ -1,19 +1,54 @@
-//! Load balancer for distributing requests across multiple providers.
+//! Load balancer for distributing requests across multiple providers, and
+//! across the deployments of a model on each provider.
 //!
 //! Supports multiple strategies:
-//! - Round-robin: Distribute requests evenly across providers
-//! - Fallback: Always try the first provider, only switch on 429
+//! - Round-robin: Distribute requests evenly across providers and deployments
+//! - Fallback: Always try the first provider and deployment, only switch on 429
+//!
+//! Providers can be disabled or drained at runtime through the admin API.
 
-use std::sync::Arc;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use crate::config::{LoadBalancingStrategy, Provider};
 
+/// Whether a provider receives requests, as set through the admin API
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderMode {
+    #[default]
+    Enabled,
+    Disabled,
+    /// Receives no new requests; becomes `Disabled` once the requests in
+    /// flight have completed
+    Draining,
+}
+
+/// Counts a request as in flight on a provider until dropped
+#[derive(Debug)]
+pub struct InFlightGuard {
+    count: Arc<AtomicUsize>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 /// Load balancer that distributes requests across multiple providers.
 #[derive(Debug, Clone)]
 pub struct LoadBalancer {
     providers: Arc<Vec<Provider>>,
     current_index: Arc<AtomicUsize>,
+    /// Round-robin position per (provider, model)
+    deployment_indexes: Arc<Mutex<HashMap<(String, String), usize>>>,
+    /// Runtime mode per provider name; absent means enabled
+    modes: Arc<Mutex<HashMap<String, ProviderMode>>>,
+    /// Requests in flight per provider name
+    in_flight: Arc<Mutex<HashMap<String, Arc<AtomicUsize>>>>,
     strategy: LoadBalancingStrategy,
 }
 
@@ -27,10 +62,70 @@ impl LoadBalancer {
         Self {
             providers: Arc::new(enabled_providers),
             current_index: Arc::new(AtomicUsize::new(0)),
+            deployment_indexes: Arc::new(Mutex::new(HashMap::new())),
+            modes: Arc::new(Mutex::new(HashMap::new())),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
             strategy,
         }
     }
 
+    /// Create a load balancer for a reloaded provider list.
+    /// The round-robin positions, runtime modes and in-flight counts are
+    /// carried over from this balancer.
+    pub fn reconfigured(&self, providers: Vec<Provider>, strategy: LoadBalancingStrategy) -> Self {
+        Self {
+            current_index: self.current_index.clone(),
+            deployment_indexes: self.deployment_indexes.clone(),
+            modes: self.modes.clone(),
+            in_flight: self.in_flight.clone(),
+            ..Self::new(providers, strategy)
+        }
+    }
+
+    /// The runtime mode of a provider. A draining provider without requests
+    /// in flight is reported, and from then on kept, as disabled.
+    pub fn provider_mode(&self, name: &str) -> ProviderMode {
+        let mut modes = self.modes.lock().unwrap();
+        let mode = modes.get(name).copied().unwrap_or_default();
+        if mode == ProviderMode::Draining && self.in_flight(name) == 0 {
+            modes.insert(name.to_string(), ProviderMode::Disabled);
+            return ProviderMode::Disabled;
+        }
+        mode
+    }
+
+    /// Change the runtime mode of a provider
+    pub fn set_provider_mode(&self, name: &str, mode: ProviderMode) {
+        let mut modes = self.modes.lock().unwrap();
+        if mode == ProviderMode::Enabled {
+            modes.remove(name);
+        } else {
+            modes.insert(name.to_string(), mode);
+        }
+    }
+
+    /// Number of requests in flight on a provider
+    pub fn in_flight(&self, name: &str) -> usize {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .get(name)
+            .map_or(0, |count| count.load(Ordering::SeqCst))
+    }
+
+    /// Count a request as in flight on a provider until the guard is dropped
+    pub fn track(&self, name: &str) -> InFlightGuard {
+        let count = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .clone();
+        count.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard { count }
+    }
+
     /// Get the load balancing strategy.
     pub fn strategy(&self) -> &LoadBalancingStrategy {
         &self.strategy
@@ -71,8 +166,10 @@ impl LoadBalancer {
     ///   then advances the index for the next request.
     /// - `Fallback`: Always returns providers in their original order (first provider first),
     ///   does not advance any index.
+    ///
+    /// Providers disabled or draining at runtime are left out.
     pub fn get_ordered_providers(&self) -> Vec<&Provider> {
-        match self.strategy {
+        let providers = match self.strategy {
             LoadBalancingStrategy::RoundRobin => {
                 let start = self.current_index.fetch_add(1, Ordering::SeqCst);
                 self.get_providers_from(start)
@@ -81,7 +178,42 @@ impl LoadBalancer {
                 // Always start from the first provider
                 self.providers.iter().collect()
             }
+        };
+        let modes = self.modes.lock().unwrap();
+        providers
+            .into_iter()
+            .filter(|p| modes.get(&p.name).is_none())
+            .collect()
+    }
+
+    /// Order the deployments of a model on a provider according to the
+    /// configured strategy, like [`Self::get_ordered_providers`]. Each
+    /// (provider, model) pair keeps its own round-robin position.
+    pub fn order_deployments<T>(
+        &self,
+        provider_name: &str,
+        model: &str,
+        mut deployments: Vec<T>,
+    ) -> Vec<T> {
+        if deployments.len() < 2 || self.strategy == LoadBalancingStrategy::Fallback {
+            return deployments;
         }
+
+        let start = {
+            let mut indexes = self
+                .deployment_indexes
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            let index = indexes
+                .entry((provider_name.to_string(), model.to_string()))
+                .or_default();
+            let start = *index;
+            *index = index.wrapping_add(1);
+            start
+        };
+        let len = deployments.len();
+        deployments.rotate_left(start % len);
+        deployments
     }
 
     /// Get the number of enabled providers.
@@ -115,10 +247,15 @@ mod tests {
             uaa_token_url: format!("https://{}.example.com/oauth/token", name),
             uaa_client_id: format!("{}-client", name),
             uaa_client_secret: format!("{}-secret", name),
+            certificate: None,
+            key: None,
             genai_api_url: format!("https://api.{}.example.com", name),
             resource_group: "default".to_string(),
+            resource_groups: Vec::new(),
             weight: 1,
             enabled,
+            http: Default::default(),
+            timeouts: Default::default(),
         }
     }
 
@@ -235,4 +372,117 @@ mod tests {
         let fb_balancer = LoadBalancer::new(providers, LoadBalancingStrategy::Fallback);
         assert_eq!(fb_balancer.strategy(), &LoadBalancingStrategy::Fallback);
     }
+
+    #[test]
+    fn test_reconfigured_keeps_rotation() {
+        let balancer = LoadBalancer::new(
+            vec![
+                create_test_provider("provider1", true),
+                create_test_provider("provider2", true),
+            ],
+            LoadBalancingStrategy::RoundRobin,
+        );
+        balancer.next();
+
+        let reloaded = balancer.reconfigured(
+            vec![
+                create_test_provider("provider1", true),
+                create_test_provider("provider2", true),
+                create_test_provider("provider3", false),
+            ],
+            LoadBalancingStrategy::Fallback,
+        );
+
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(reloaded.strategy(), &LoadBalancingStrategy::Fallback);
+        assert_eq!(reloaded.next().unwrap().name, "provider2");
+        // The original balancer still has its own provider list
+        assert_eq!(balancer.len(), 2);
+        assert_eq!(balancer.next().unwrap().name, "provider1");
+    }
+
+    #[test]
+    fn test_order_deployments() {
+        let providers = vec![
+            create_test_provider("provider1", true),
+            create_test_provider("provider2", true),
+        ];
+        let deployments = || vec!["d1".to_string(), "d2".to_string(), "d3".to_string()];
+
+        let balancer = LoadBalancer::new(providers.clone(), LoadBalancingStrategy::RoundRobin);
+        assert_eq!(
+            balancer.order_deployments("provider1", "gpt-4o", deployments()),
+            ["d1", "d2", "d3"]
+        );
+        assert_eq!(
+            balancer.order_deployments("provider1", "gpt-4o", deployments()),
+            ["d2", "d3", "d1"]
+        );
+        // Other models and providers rotate independently
+        assert_eq!(
+            balancer.order_deployments("provider2", "gpt-4o", deployments()),
+            ["d1", "d2", "d3"]
+        );
+        assert_eq!(
+            balancer.order_deployments("provider1", "gpt-4.1", deployments()),
+            ["d1", "d2", "d3"]
+        );
+
+        // The position survives a reload
+        let reloaded = balancer.reconfigured(providers.clone(), LoadBalancingStrategy::RoundRobin);
+        assert_eq!(
+            reloaded.order_deployments("provider1", "gpt-4o", deployments()),
+            ["d3", "d1", "d2"]
+        );
+
+        let fallback = LoadBalancer::new(providers, LoadBalancingStrategy::Fallback);
+        for _ in 0..2 {
+            assert_eq!(
+                fallback.order_deployments("provider1", "gpt-4o", deployments()),
+                ["d1", "d2", "d3"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_runtime_provider_modes() {
+        let balancer = LoadBalancer::new(
+            vec![
+                create_test_provider("provider1", true),
+                create_test_provider("provider2", true),
+            ],
+            LoadBalancingStrategy::Fallback,
+        );
+        let names = |balancer: &LoadBalancer| -> Vec<String> {
+            balancer
+                .get_ordered_providers()
+                .iter()
+                .map(|p| p.name.clone())
+                .collect()
+        };
+
+        balancer.set_provider_mode("provider1", ProviderMode::Disabled);
+        assert_eq!(names(&balancer), ["provider2"]);
+        balancer.set_provider_mode("provider1", ProviderMode::Enabled);
+        assert_eq!(names(&balancer), ["provider1", "provider2"]);
+
+        // Draining lasts until the requests in flight are done
+        let request = balancer.track("provider2");
+        balancer.set_provider_mode("provider2", ProviderMode::Draining);
+        assert_eq!(names(&balancer), ["provider1"]);
+        assert_eq!(balancer.provider_mode("provider2"), ProviderMode::Draining);
+        assert_eq!(balancer.in_flight("provider2"), 1);
+        drop(request);
+        assert_eq!(balancer.provider_mode("provider2"), ProviderMode::Disabled);
+
+        // Modes survive a reload
+        let reloaded = balancer.reconfigured(
+            vec![
+                create_test_provider("provider1", true),
+                create_test_provider("provider2", true),
+            ],
+            LoadBalancingStrategy::Fallback,
+        );
+        assert_eq!(names(&reloaded), ["provider1"]);
+    }
 }
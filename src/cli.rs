@@ -1,16 +1,23 @@
 use anyhow::{Context, Result};
+use axum::extract::DefaultBodyLimit;
 use clap::{Arg, Command};
-use std::net::SocketAddr;
+use std::sync::Arc;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing_subscriber::{EnvFilter, fmt};
-use axum::extract::DefaultBodyLimit;
 
 use crate::{
+    auth::Authenticator,
     balancer::LoadBalancer,
-    commands::CommandHandler,
+    commands::{
+        CommandHandler, InitOptions, config_schema, encrypt_secret, generate_secret_key,
+        init_config, validate_config,
+    },
     config::Config,
+    constants::config::DEFAULT_RESOURCE_GROUP,
+    listener::{self, ListenAddress},
     registry::ModelRegistry,
-    routes::{AppState, create_router},
+    reload::ConfigReloader,
+    routes::{AppState, Snapshot, create_router},
     token::TokenManager,
 };
 
@@ -20,7 +27,66 @@ impl Cli {
     pub async fn run() -> Result<()> {
         let matches = Self::build_command().get_matches();
 
+        // Secret helpers don't need a loadable configuration
+        if let Some(("secret", secret_matches)) = matches.subcommand() {
+            return match secret_matches.subcommand() {
+                Some(("generate-key", key_matches)) => {
+                    generate_secret_key(key_matches.get_one::<String>("out").map(|s| s.as_str()))
+                }
+                Some(("encrypt", encrypt_matches)) => encrypt_secret(
+                    encrypt_matches
+                        .get_one::<String>("key-file")
+                        .map(|s| s.as_str()),
+                ),
+                _ => {
+                    eprintln!(
+                        "Unknown secret subcommand. Use 'acr secret generate-key' or 'acr secret encrypt'"
+                    );
+                    std::process::exit(1);
+                }
+            };
+        }
+
         let config_path = matches.get_one::<String>("config").map(|s| s.as_str());
+
+        // Config commands inspect the config file themselves
+        if let Some(("config", config_matches)) = matches.subcommand() {
+            return match config_matches.subcommand() {
+                Some(("validate", validate_matches)) => {
+                    validate_config(config_path, validate_matches.get_flag("online")).await
+                }
+                Some(("schema", schema_matches)) => {
+                    config_schema(schema_matches.get_one::<String>("out").map(|s| s.as_str()))
+                }
+                Some(("init", init_matches)) => {
+                    init_config(InitOptions {
+                        service_key_files: init_matches
+                            .get_many::<String>("service-key")
+                            .unwrap_or_default()
+                            .map(|s| s.as_str())
+                            .collect(),
+                        out: match init_matches.get_one::<String>("out") {
+                            Some(out) => out.clone(),
+                            None => Config::file_path(config_path)?,
+                        },
+                        resource_group: init_matches
+                            .get_one::<String>("resource-group")
+                            .map(|s| s.as_str())
+                            .unwrap_or(DEFAULT_RESOURCE_GROUP),
+                        api_key_count: *init_matches.get_one::<usize>("api-keys").unwrap_or(&1),
+                        force: init_matches.get_flag("force"),
+                    })
+                    .await
+                }
+                _ => {
+                    eprintln!(
+                        "Unknown config subcommand. Use 'acr config validate', 'acr config init' or 'acr config schema'"
+                    );
+                    std::process::exit(1);
+                }
+            };
+        }
+
         let config = Config::load(config_path).context("Failed to load configuration")?;
 
         // Handle CLI commands
@@ -49,6 +115,17 @@ impl Cli {
                         std::process::exit(1);
                     }
                 }
+                ("models", models_matches) => {
+                    if let Some(("resolve", resolve_matches)) = models_matches.subcommand() {
+                        let name = resolve_matches
+                            .get_one::<String>("name")
+                            .expect("name is required");
+                        return handler.resolve_model(name).await;
+                    } else {
+                        eprintln!("Unknown models subcommand. Use 'acr models resolve <name>'");
+                        std::process::exit(1);
+                    }
+                }
                 _ => {
                     eprintln!("Unknown command");
                     std::process::exit(1);
@@ -97,6 +174,111 @@ impl Cli {
                         ),
                     ),
             )
+            .subcommand(
+                Command::new("models")
+                    .about("Inspect model resolution")
+                    .subcommand(
+                        Command::new("resolve")
+                            .about("Show which model a requested name resolves to and why")
+                            .arg(
+                                Arg::new("name")
+                                    .required(true)
+                                    .value_name("NAME")
+                                    .help("Model name as sent by clients"),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new("config")
+                    .about("Inspect the configuration file")
+                    .subcommand(
+                        Command::new("validate")
+                            .about("Check the configuration and report all problems")
+                            .arg(
+                                Arg::new("online")
+                                    .long("online")
+                                    .action(clap::ArgAction::SetTrue)
+                                    .help("Also fetch tokens and check that models are deployed"),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new("schema")
+                            .about("Print the JSON Schema of the config file")
+                            .arg(
+                                Arg::new("out")
+                                    .short('o')
+                                    .long("out")
+                                    .value_name("FILE")
+                                    .help("Write the schema to a file instead of stdout"),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new("init")
+                            .about("Generate a config file from AI Core service keys")
+                            .arg(
+                                Arg::new("service-key")
+                                    .short('s')
+                                    .long("service-key")
+                                    .value_name("FILE")
+                                    .required(true)
+                                    .action(clap::ArgAction::Append)
+                                    .help("Service key JSON file, one per provider (repeatable)"),
+                            )
+                            .arg(
+                                Arg::new("out")
+                                    .short('o')
+                                    .long("out")
+                                    .value_name("FILE")
+                                    .help("Config file to write (default: --config path or ~/.aicore/config.yaml)"),
+                            )
+                            .arg(
+                                Arg::new("resource-group")
+                                    .short('r')
+                                    .long("resource-group")
+                                    .value_name("RESOURCE_GROUP")
+                                    .help("Resource group of the deployments (default: default)"),
+                            )
+                            .arg(
+                                Arg::new("api-keys")
+                                    .long("api-keys")
+                                    .value_name("COUNT")
+                                    .value_parser(clap::value_parser!(usize))
+                                    .help("Number of router API keys to generate (default: 1)"),
+                            )
+                            .arg(
+                                Arg::new("force")
+                                    .long("force")
+                                    .action(clap::ArgAction::SetTrue)
+                                    .help("Overwrite an existing config file"),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new("secret")
+                    .about("Manage encrypted config values")
+                    .subcommand(
+                        Command::new("generate-key")
+                            .about("Generate a secret key file")
+                            .arg(
+                                Arg::new("out")
+                                    .short('o')
+                                    .long("out")
+                                    .value_name("FILE")
+                                    .help("Key file to write (default: ~/.aicore/secret.key)"),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new("encrypt")
+                            .about("Encrypt a value read from stdin")
+                            .arg(
+                                Arg::new("key-file")
+                                    .short('k')
+                                    .long("key-file")
+                                    .value_name("FILE")
+                                    .help("Key file to use (default: $ACR_SECRET_KEY_FILE or ~/.aicore/secret.key)"),
+                            ),
+                    ),
+            )
     }
 
     async fn run_server(matches: clap::ArgMatches, mut config: Config) -> Result<()> {
@@ -114,21 +296,39 @@ impl Cli {
             config.port = *port;
         }
 
-        tracing::info!("Starting AI Core Router on port {}", config.port);
+        tracing::info!("Starting AI Core Router");
         tracing::info!("Configured providers: {}", config.providers.len());
         for provider in &config.providers {
             tracing::info!(
-                "  Provider '{}': {} (resource_group: {}, enabled: {})",
+                "  Provider '{}': {} (resource_groups: {}, enabled: {}, auth: {})",
                 provider.name,
                 provider.genai_api_url,
-                provider.resource_group,
-                provider.enabled
+                provider.resource_group_patterns().join(", "),
+                provider.enabled,
+                if provider.uses_client_certificate() {
+                    "x509"
+                } else {
+                    "client secret"
+                }
             );
         }
         tracing::info!("Configured API keys: {}", config.api_keys.len());
+        if let Some(ref jwt) = config.jwt_auth {
+            tracing::info!("JWT authentication enabled (issuer: {})", jwt.issuer);
+        }
+
+        // Bind early so that address problems surface before the registry starts
+        let mut bound = Vec::new();
+        for listener in config.listeners() {
+            let address = ListenAddress::parse(&listener.address)?;
+            let sockets = listener::bind(&address, listener.tls.as_ref()).await?;
+            bound.push((listener, sockets));
+        }
+
+        let authenticator = Authenticator::from_config(&config);
 
         // Create token manager with API keys
-        let token_manager = TokenManager::new(config.api_keys.clone());
+        let token_manager = TokenManager::new();
 
         // Create load balancer with providers and configured strategy
         let load_balancer =
@@ -139,58 +339,71 @@ impl Cli {
             return Err(anyhow::anyhow!("No enabled providers configured"));
         }
 
-        let client = reqwest::Client::new();
-
         // Create and start model registry
         tracing::info!(
             "Initializing model registry with refresh interval: {}s",
             config.refresh_interval_secs
         );
-        let model_registry = ModelRegistry::new(
-            config.models.clone(),
-            config.fallback_models.clone(),
-            config.providers.clone(),
-            token_manager.clone(),
-            config.refresh_interval_secs,
-        );
+        let model_registry = ModelRegistry::from_config(&config, token_manager.clone());
         model_registry
             .start()
             .await
             .context("Failed to start model registry")?;
 
-        let state = AppState {
-            config: config.clone(),
-            model_registry,
+        let state = AppState::new(
+            Snapshot {
+                config: config.clone(),
+                authenticator,
+                model_registry,
+                load_balancer,
+            },
             token_manager,
-            load_balancer,
-            client,
-        };
-
-        // Build base app with common layers
-        let base_app = create_router(state)
-            .layer(CorsLayer::permissive())
-            .layer(TraceLayer::new_for_http());
-
-        // Apply configurable body limit if provided; otherwise use Axum's default (2 MiB for Json)
-        let app = match config.request_body_limit {
-            Some(limit) => {
-                tracing::info!("Request body limit set to {} bytes", limit);
-                base_app.layer(DefaultBodyLimit::max(limit))
-            }
+        );
+
+        let reloader = Arc::new(ConfigReloader::new(
+            matches.get_one::<String>("config").cloned(),
+            matches.get_one::<u16>("port").copied(),
+            state.clone(),
+        ));
+        reloader
+            .spawn()
+            .context("Failed to start configuration watcher")?;
+
+        match config.request_body_limit {
+            Some(limit) => tracing::info!("Request body limit set to {} bytes", limit),
             None => {
-                tracing::info!("Request body limit not set; using Axum default (2 MiB for Json)");
-                base_app
+                tracing::info!("Request body limit not set; using Axum default (2 MiB for Json)")
             }
-        };
+        }
 
-        let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
-        let listener = tokio::net::TcpListener::bind(addr)
-            .await
-            .context("Failed to bind to address")?;
+        let mut servers = Vec::new();
+        for (listener, sockets) in bound {
+            // Build base app with common layers
+            let base_app = create_router(state.clone(), &listener.routes)
+                .layer(CorsLayer::permissive())
+                .layer(TraceLayer::new_for_http());
+
+            // Apply configurable body limit if provided; otherwise use Axum's default (2 MiB for Json)
+            let app = match config.request_body_limit {
+                Some(limit) => base_app.layer(DefaultBodyLimit::max(limit)),
+                None => base_app,
+            };
 
-        tracing::info!("Server listening on {}", addr);
+            let tls = match listener.tls {
+                Some(ref tls) if tls.client_ca.is_some() => ", TLS with client certificates",
+                Some(_) => ", TLS",
+                None => "",
+            };
+            tracing::info!(
+                "Server listening on {} (routes: {:?}{})",
+                listener.address,
+                listener.routes,
+                tls
+            );
+            servers.extend(sockets.into_iter().map(|socket| socket.serve(app.clone())));
+        }
 
-        axum::serve(listener, app).await.context("Server error")?;
+        futures::future::try_join_all(servers).await?;
 
         Ok(())
     }
@@ -1,9 +1,11 @@
 use anyhow::{Context, Result};
-use reqwest::Client;
 use serde::Deserialize;
 
 use crate::{
     config::{Config, Provider},
+    constants::deployment::RUNNING_STATUS,
+    http,
+    registry::glob_matches,
     token::TokenManager,
 };
 
@@ -89,6 +91,11 @@ impl Deployment {
         (None, None)
     }
 
+    /// When the deployment started, or was created if it has not started yet
+    pub fn started_at(&self) -> &str {
+        self.start_time.as_deref().unwrap_or(&self.created_at)
+    }
+
     pub fn get_aicore_model_name(&self) -> Option<String> {
         if let Some(details) = &self.details
             && let Some(resources) = &details.resources
@@ -104,6 +111,28 @@ impl Deployment {
     }
 }
 
+/// Running deployments across a provider's resource groups
+#[derive(Debug, Default)]
+pub struct ProviderDeployments {
+    /// Running deployments by AI Core model name
+    pub models: std::collections::HashMap<String, Vec<RunningDeployment>>,
+    /// Resource groups whose deployments were listed
+    pub listed_groups: Vec<String>,
+    /// Resource groups whose deployments could not be listed, with the error
+    pub failed_groups: Vec<(String, anyhow::Error)>,
+}
+
+/// A running deployment, with what the router needs to choose between
+/// deployments of the same model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunningDeployment {
+    pub id: String,
+    pub resource_group: String,
+    pub model_version: Option<String>,
+    pub configuration_name: Option<String>,
+    pub started_at: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct AiCoreClientConfig {
     pub genai_api_url: String,
@@ -113,7 +142,6 @@ pub struct AiCoreClientConfig {
 
 #[derive(Debug, Clone)]
 pub struct AiCoreClient {
-    client: Client,
     config: AiCoreClientConfig,
     token_manager: TokenManager,
 }
@@ -121,7 +149,6 @@ pub struct AiCoreClient {
 impl AiCoreClient {
     pub fn new(config: AiCoreClientConfig, token_manager: TokenManager) -> Self {
         Self {
-            client: Client::new(),
             config,
             token_manager,
         }
@@ -149,21 +176,22 @@ impl AiCoreClient {
 
     async fn get_token(&self) -> Result<String> {
         self.token_manager
-            .get_token_for_provider("internal", &self.config.provider)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Failed to get authentication token"))
+            .get_token(&self.config.provider)
+            .await
+            .context("Failed to get authentication token")
     }
 
     pub async fn list_resource_groups(&self) -> Result<ResourceGroupList> {
         let token = self.get_token().await?;
         let url = format!("{}/v2/admin/resourceGroups", self.config.genai_api_url);
 
-        let response = self
-            .client
+        let request = self
+            .client()?
             .get(&url)
             .header("Authorization", format!("Bearer {token}"))
-            .header("Content-Type", "application/json")
-            .send()
+            .header("Content-Type", "application/json");
+        let response = self
+            .send(request)
             .await
             .context("Failed to request resource groups")?;
 
@@ -190,7 +218,7 @@ impl AiCoreClient {
         let url = format!("{}/v2/lm/deployments", self.config.genai_api_url);
 
         let mut request = self
-            .client
+            .client()?
             .get(&url)
             .header("Authorization", format!("Bearer {token}"))
             .header("Content-Type", "application/json");
@@ -198,8 +226,8 @@ impl AiCoreClient {
         let rg = resource_group.unwrap_or(&self.config.resource_group);
         request = request.header("AI-Resource-Group", rg);
 
-        let response = request
-            .send()
+        let response = self
+            .send(request)
             .await
             .context("Failed to request deployments")?;
 
@@ -233,7 +261,7 @@ impl AiCoreClient {
         );
 
         let mut request = self
-            .client
+            .client()?
             .get(&url)
             .header("Authorization", format!("Bearer {token}"))
             .header("Content-Type", "application/json");
@@ -241,8 +269,8 @@ impl AiCoreClient {
         let rg = resource_group.unwrap_or(&self.config.resource_group);
         request = request.header("AI-Resource-Group", rg);
 
-        let response = request
-            .send()
+        let response = self
+            .send(request)
             .await
             .context("Failed to request deployment")?;
 
@@ -268,26 +296,234 @@ impl AiCoreClient {
         &self.config
     }
 
-    pub fn get_client(&self) -> &Client {
-        &self.client
+    /// Shared HTTP client configured with the provider's `http` settings
+    pub fn client(&self) -> Result<reqwest::Client> {
+        http::provider_client(&self.config.provider)
+    }
+
+    /// Send a request with the provider's first-byte and total timeouts.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let timeouts = self.config.provider.timeouts.resolve();
+        http::send(request.timeout(timeouts.total), timeouts.first_byte).await
     }
 
+    /// Map each AI Core model name to all its running deployments, in the
+    /// order they are listed.
     pub async fn build_model_to_deployment_mapping(
         &self,
         resource_group: Option<&str>,
-    ) -> Result<std::collections::HashMap<String, String>> {
+    ) -> Result<std::collections::HashMap<String, Vec<RunningDeployment>>> {
         let deployments = self.list_deployments(resource_group).await?;
+        let resource_group = resource_group.unwrap_or(&self.config.resource_group);
 
-        let mut mapping = std::collections::HashMap::new();
+        let mut mapping: std::collections::HashMap<String, Vec<RunningDeployment>> =
+            std::collections::HashMap::new();
 
         for deployment in &deployments.resources {
-            if deployment.status == "RUNNING"
-                && let Some(model_name) = deployment.get_aicore_model_name()
+            if deployment.status == RUNNING_STATUS
+                && let (Some(model_name), model_version) = deployment.get_model_info()
             {
-                mapping.insert(model_name, deployment.id.clone());
+                mapping
+                    .entry(model_name)
+                    .or_default()
+                    .push(RunningDeployment {
+                        id: deployment.id.clone(),
+                        resource_group: resource_group.to_string(),
+                        model_version,
+                        configuration_name: deployment.configuration_name.clone(),
+                        started_at: deployment.started_at().to_string(),
+                    });
             }
         }
 
         Ok(mapping)
     }
+
+    /// The provider's resource groups, with globs expanded against the
+    /// tenant's resource groups.
+    pub async fn provider_resource_groups(&self) -> Result<Vec<String>> {
+        let patterns = self.config.provider.resource_group_patterns();
+        if !patterns.iter().any(|pattern| pattern.contains('*')) {
+            return Ok(patterns.into_iter().map(str::to_string).collect());
+        }
+
+        let available = self.list_resource_groups().await?;
+        let mut groups: Vec<String> = Vec::new();
+        for pattern in patterns {
+            if !pattern.contains('*') {
+                groups.push(pattern.to_string());
+                continue;
+            }
+            groups.extend(
+                available
+                    .resources
+                    .iter()
+                    .map(|group| &group.resource_group_id)
+                    .filter(|id| glob_matches(pattern, id).is_some())
+                    .cloned(),
+            );
+        }
+        let mut seen = std::collections::HashSet::new();
+        groups.retain(|group| seen.insert(group.clone()));
+        Ok(groups)
+    }
+
+    /// [`Self::build_model_to_deployment_mapping`] across all the provider's
+    /// resource groups. Groups that fail are logged and returned with the
+    /// deployments of the others, unless all fail.
+    pub async fn build_provider_deployment_mapping(&self) -> Result<ProviderDeployments> {
+        let groups = self.provider_resource_groups().await?;
+        let mut deployments = ProviderDeployments::default();
+
+        for group in groups {
+            match self.build_model_to_deployment_mapping(Some(&group)).await {
+                Ok(group_mapping) => {
+                    for (model, running) in group_mapping {
+                        deployments.models.entry(model).or_default().extend(running);
+                    }
+                    deployments.listed_groups.push(group);
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "Provider '{}': failed to list deployments in resource group '{}': {:#}",
+                        self.config.provider.name,
+                        group,
+                        e
+                    );
+                    deployments.failed_groups.push((group, e));
+                }
+            }
+        }
+
+        if deployments.listed_groups.is_empty()
+            && let Some((_, e)) = deployments.failed_groups.pop()
+        {
+            return Err(e);
+        }
+        Ok(deployments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Json, Router,
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+    };
+    use serde_json::json;
+
+    /// Deployments of a resource group, keyed by its `AI-Resource-Group` header
+    async fn list_deployments(headers: HeaderMap) -> Result<Json<serde_json::Value>, StatusCode> {
+        let deployment = |id: &str, status: &str| {
+            json!({
+                "id": id,
+                "createdAt": "2025-01-01T00:00:00Z",
+                "modifiedAt": "2025-01-01T00:00:00Z",
+                "status": status,
+                "scenarioId": "foundation-models",
+                "configurationId": "c",
+                "details": {"resources": {"backendDetails": {"model": {"name": "gpt-4o"}}}}
+            })
+        };
+        let resources = match headers
+            .get("AI-Resource-Group")
+            .and_then(|v| v.to_str().ok())
+        {
+            Some("team-a") => vec![deployment("d-a", "RUNNING")],
+            Some("team-b") => vec![
+                deployment("d-b", "RUNNING"),
+                deployment("d-b-stopped", "STOPPED"),
+            ],
+            _ => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        };
+        Ok(Json(
+            json!({"count": resources.len(), "resources": resources}),
+        ))
+    }
+
+    /// Serve UAA tokens, the tenant's resource groups and their deployments;
+    /// listing `team-broken` fails. Returns the base URL.
+    async fn start_fake_aicore() -> String {
+        let group = |id: &str| {
+            json!({
+                "resourceGroupId": id,
+                "tenantId": "t",
+                "createdAt": "2025-01-01T00:00:00Z",
+                "status": "PROVISIONED"
+            })
+        };
+        let groups = json!({
+            "count": 4,
+            "resources": [group("team-a"), group("team-b"), group("team-broken"), group("default")]
+        });
+        let app = Router::new()
+            .route(
+                "/oauth/token",
+                post(|| async { Json(json!({"access_token": "token", "expires_in": 3600})) }),
+            )
+            .route(
+                "/v2/admin/resourceGroups",
+                get(move || async move { Json(groups) }),
+            )
+            .route("/v2/lm/deployments", get(list_deployments));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    fn client(url: &str, resource_groups: &str) -> AiCoreClient {
+        let provider: Provider = serde_yaml::from_str(&format!(
+            r#"
+name: eu10
+uaa_token_url: {url}/oauth/token
+uaa_client_id: c
+uaa_client_secret: s
+genai_api_url: {url}
+resource_groups: {resource_groups}
+"#
+        ))
+        .unwrap();
+        AiCoreClient::from_provider(provider, TokenManager::new())
+    }
+
+    #[tokio::test]
+    async fn test_provider_resource_groups() {
+        let url = start_fake_aicore().await;
+
+        // Globs are expanded in the tenant's order; duplicates are dropped
+        let client = client(&url, "[team-b, team-*]");
+        assert_eq!(
+            client.provider_resource_groups().await.unwrap(),
+            ["team-b", "team-a", "team-broken"]
+        );
+
+        // The failing group is reported, and each deployment keeps its group
+        let mapping = client.build_provider_deployment_mapping().await.unwrap();
+        let deployments: Vec<(&str, &str)> = mapping.models["gpt-4o"]
+            .iter()
+            .map(|d| (d.id.as_str(), d.resource_group.as_str()))
+            .collect();
+        assert_eq!(deployments, [("d-b", "team-b"), ("d-a", "team-a")]);
+        assert_eq!(mapping.listed_groups, ["team-b", "team-a"]);
+        let failed: Vec<&str> = mapping
+            .failed_groups
+            .iter()
+            .map(|(group, _)| group.as_str())
+            .collect();
+        assert_eq!(failed, ["team-broken"]);
+    }
+
+    #[tokio::test]
+    async fn test_provider_deployment_mapping_fails_when_all_groups_fail() {
+        let url = start_fake_aicore().await;
+        let client = client(&url, "[team-broken, missing]");
+        assert_eq!(
+            client.provider_resource_groups().await.unwrap(),
+            ["team-broken", "missing"]
+        );
+        assert!(client.build_provider_deployment_mapping().await.is_err());
+    }
 }
@@ -1,7 +1,16 @@
 //! CLI command handlers for administrative operations.
 
-use crate::{client::AiCoreClient, config::Config, token::TokenManager};
-use anyhow::Result;
+use crate::{
+    client::AiCoreClient,
+    config::{Config, ConfigFile},
+    init,
+    registry::{ModelRegistry, ResolvedBy},
+    secrets,
+    token::TokenManager,
+    validate,
+};
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
 
 pub struct CommandHandler {
     client: AiCoreClient,
@@ -11,7 +20,7 @@ pub struct CommandHandler {
 impl CommandHandler {
     pub fn new(config: Config) -> Self {
         // Create a token manager for CLI operations
-        let token_manager = TokenManager::new(config.api_keys.clone());
+        let token_manager = TokenManager::new();
 
         // Use the first provider for CLI commands
         let provider = config
@@ -103,4 +112,231 @@ impl CommandHandler {
 
         Ok(())
     }
+
+    /// Show how a requested model name resolves and which alias matched.
+    pub async fn resolve_model(&self, name: &str) -> Result<()> {
+        let token_manager = TokenManager::new();
+        let registry = ModelRegistry::from_config(&self.config, token_manager);
+        let resolution = registry.resolve_model(name).await;
+
+        println!("'{name}' resolves to '{}'", resolution.model);
+        match resolution.resolved_by {
+            ResolvedBy::Name => println!("Matched by: configured model name"),
+            ResolvedBy::Discovered => println!("Matched by: discovered deployment"),
+            ResolvedBy::Alias { ref rule } => println!("Matched by: {rule}"),
+            ResolvedBy::Fallback { ref family } => {
+                println!("Matched by: fallback model for the {family} family")
+            }
+            ResolvedBy::Unchanged => {
+                println!("No model, alias or fallback matched; the name is used as requested")
+            }
+        }
+
+        let matches = registry.alias_matches(name);
+        if matches.is_empty() {
+            return Ok(());
+        }
+        println!("\nMatching aliases, most specific first:");
+        for (i, matched) in matches.iter().enumerate() {
+            let marker = if i == 0 && matches!(resolution.resolved_by, ResolvedBy::Alias { .. }) {
+                "*"
+            } else {
+                " "
+            };
+            println!(
+                "  {marker} {} -> '{}' ({})",
+                matched.rule,
+                matched.target,
+                matched.specificity()
+            );
+        }
+        if let [first, second, ..] = matches.as_slice()
+            && first.ties_with(second)
+            && first.target != second.target
+        {
+            println!(
+                "\nThe first two aliases are equally specific; '{}' wins as the alphabetically first target.",
+                first.target
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Write a new secret key file for encrypting config values.
+pub fn generate_secret_key(out: Option<&str>) -> Result<()> {
+    let path = match out {
+        Some(path) => std::path::PathBuf::from(path),
+        None => secrets::default_key_file()?,
+    };
+    if path.exists() {
+        return Err(anyhow::anyhow!(
+            "Secret key file already exists: {}",
+            path.display()
+        ));
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(&path)
+        .with_context(|| format!("Failed to create secret key file: {}", path.display()))?;
+    writeln!(file, "{}", secrets::generate_key())?;
+
+    println!("Secret key written to {}", path.display());
+    Ok(())
+}
+
+/// Encrypt a value read from stdin and print it in `enc:v1:` form.
+pub fn encrypt_secret(key_file: Option<&str>) -> Result<()> {
+    let path = match key_file {
+        Some(path) => std::path::PathBuf::from(path),
+        None => match std::env::var(secrets::SECRET_KEY_FILE_ENV) {
+            Ok(path) => std::path::PathBuf::from(path),
+            Err(_) => secrets::default_key_file()?,
+        },
+    };
+    let key = secrets::load_key(&path)?;
+
+    let mut value = String::new();
+    std::io::stdin()
+        .read_to_string(&mut value)
+        .context("Failed to read value from stdin")?;
+    let value = value.trim_end_matches(['\r', '\n']);
+    if value.is_empty() {
+        return Err(anyhow::anyhow!("No value provided on stdin"));
+    }
+
+    println!("{}", secrets::encrypt(value, &key)?);
+    Ok(())
+}
+
+/// Check a config file and print every problem found.
+pub async fn validate_config(config_path: Option<&str>, online: bool) -> Result<()> {
+    let path = Config::file_path(config_path)?;
+    println!("Validating {path}...");
+
+    let mut report = validate::validate_file(&path);
+    if online && let Some(config) = report.config.clone() {
+        println!("Checking providers and model deployments...");
+        validate::check_online(&config, &mut report).await;
+    }
+
+    for diagnostic in &report.diagnostics {
+        println!("  {diagnostic}");
+    }
+
+    if report.has_errors() {
+        return Err(anyhow::anyhow!(
+            "Configuration is invalid: {} error(s), {} warning(s)",
+            report.error_count(),
+            report.warning_count()
+        ));
+    }
+    println!(
+        "Configuration is valid ({} warning(s))",
+        report.warning_count()
+    );
+    Ok(())
+}
+
+/// Options for `acr config init`
+pub struct InitOptions<'a> {
+    pub service_key_files: Vec<&'a str>,
+    pub out: String,
+    pub resource_group: &'a str,
+    pub api_key_count: usize,
+    pub force: bool,
+}
+
+/// Generate a config file from service keys and the models deployed on them.
+pub async fn init_config(options: InitOptions<'_>) -> Result<()> {
+    let out = std::path::Path::new(&options.out);
+    if out.exists() && !options.force {
+        return Err(anyhow::anyhow!(
+            "Config file already exists: {} (use --force to overwrite)",
+            out.display()
+        ));
+    }
+
+    let mut providers = Vec::new();
+    let mut deployments = Vec::new();
+    for file in options.service_key_files {
+        let path = std::path::absolute(file)
+            .with_context(|| format!("Invalid service key path: {file}"))?;
+        let provider = init::provider_for_service_key(&path, options.resource_group)?;
+
+        println!("Fetching deployments for provider '{}'...", provider.name);
+        match init::list_provider_deployments(&provider).await {
+            Ok(found) => deployments.push(found),
+            Err(e) => {
+                eprintln!(
+                    "Warning: could not list deployments for '{}': {:#}",
+                    provider.name, e
+                );
+                deployments.push(Vec::new());
+            }
+        }
+        providers.push(provider);
+    }
+
+    let api_keys = (0..options.api_key_count)
+        .map(|_| init::generate_api_key())
+        .collect();
+    let config = init::build_config(providers, &deployments, api_keys);
+    let yaml = init::to_yaml(&config)?;
+
+    if let Some(parent) = out.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    // The file holds the generated API keys, so only the owner may read it
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(out)
+        .with_context(|| format!("Failed to write config file: {}", out.display()))?;
+    // `mode` only applies to new files, not ones overwritten with --force
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("Failed to restrict permissions of {}", out.display()))?;
+    }
+    write!(file, "# Generated by acr config init\n{yaml}")
+        .with_context(|| format!("Failed to write config file: {}", out.display()))?;
+
+    println!(
+        "\nWrote {} with {} provider(s) and {} model(s).",
+        out.display(),
+        config.providers.len(),
+        config.models.len()
+    );
+    println!("API keys for clients of the router:");
+    for key in &config.api_keys {
+        println!("  {key}");
+    }
+    Ok(())
+}
+
+/// Print the JSON Schema of the config file, or write it to a file.
+pub fn config_schema(out: Option<&str>) -> Result<()> {
+    let schema = serde_json::to_string_pretty(&ConfigFile::json_schema())?;
+    match out {
+        Some(path) => {
+            std::fs::write(path, format!("{schema}\n"))
+                .with_context(|| format!("Failed to write schema file: {path}"))?;
+            println!("Schema written to {path}");
+        }
+        None => println!("{schema}"),
+    }
+    Ok(())
 }
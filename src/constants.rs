@@ -3,6 +3,14 @@ pub mod deployment {
     pub const RUNNING_STATUS: &str = "RUNNING";
     pub const STOPPED_STATUS: &str = "STOPPED";
     pub const UNKNOWN_STATUS: &str = "UNKNOWN";
+    /// Error codes (`error.code`) meaning the deployment itself is gone or stopped
+    pub const UNAVAILABLE_ERROR_CODES: [&str; 3] = [
+        "DeploymentNotFound",
+        "DeploymentNotRunning",
+        "DeploymentStopped",
+    ];
+    /// Minimum time between refreshes of a provider triggered by deployment errors
+    pub const REFRESH_DEBOUNCE_SECS: u64 = 30;
 }
 
 pub mod models {
@@ -41,9 +49,38 @@ pub mod api {
     pub const MODELS_PATH: &str = "/models";
 }
 
+pub mod token {
+    /// Tokens are treated as expired this long before their actual expiry
+    pub const EXPIRY_MARGIN_SECS: i64 = 60;
+    pub const REFRESH_BACKOFF_BASE_SECS: u64 = 2;
+    pub const REFRESH_BACKOFF_MAX_SECS: u64 = 300;
+}
+
+pub mod reload {
+    /// Quiet period after a config file change before reloading
+    pub const DEBOUNCE_MS: u64 = 500;
+}
+
+pub mod timeouts {
+    pub const DEFAULT_CONNECT_SECS: u64 = 10;
+    /// Non-streaming responses of reasoning models can take minutes to start
+    pub const DEFAULT_FIRST_BYTE_SECS: u64 = 300;
+    pub const DEFAULT_TOTAL_SECS: u64 = 600;
+    pub const DEFAULT_STREAM_IDLE_SECS: u64 = 120;
+}
+
+pub mod tls {
+    /// Connections that have not completed the TLS handshake by then are dropped
+    pub const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+}
+
 pub mod config {
     pub const DEFAULT_PORT: u16 = 8900;
     pub const DEFAULT_LOG_LEVEL: &str = "info";
     pub const DEFAULT_RESOURCE_GROUP: &str = "default";
     pub const DEFAULT_REFRESH_INTERVAL_SECS: u64 = 300; // 5 minutes
+    pub const DEFAULT_REFRESH_TIMEOUT_SECS: u64 = 60;
+    pub const DEFAULT_MAX_STALENESS_SECS: u64 = 86400; // 1 day
+    pub const DEFAULT_JWKS_CACHE_SECS: u64 = 3600;
+    pub const DEFAULT_JWT_LEEWAY_SECS: u64 = 60;
 }
@@ -92,6 +92,31 @@ pub enum ProxyError {
     },
 }
 
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("Invalid API key")]
+    InvalidApiKey,
+
+    #[error("Invalid bearer token: {details}")]
+    InvalidToken { details: String },
+
+    #[error("Failed to load JWKS: {source}")]
+    JwksUnavailable {
+        #[from]
+        source: anyhow::Error,
+    },
+}
+
+/// An upstream request that did not complete in time
+#[derive(Debug, Error)]
+pub enum TimeoutError {
+    #[error("No response from upstream within {secs}s")]
+    FirstByte { secs: u64 },
+
+    #[error("Upstream stream idle for more than {secs}s")]
+    StreamIdle { secs: u64 },
+}
+
 #[derive(Debug, Error)]
 pub enum ClientError {
     #[error("Authentication failed: {details}")]
@@ -0,0 +1,379 @@
+//! Outbound HTTP clients for UAA, AI Core and JWKS calls.
+//!
+//! All outbound traffic goes through [`client`], so proxy, CA and client
+//! certificate settings apply uniformly. Clients are cached per distinct
+//! settings and share their connection pool. Requests are sent with [`send`]
+//! so that they cannot hang forever.
+
+use anyhow::{Context, Result};
+use reqwest::{Client, ClientBuilder, RequestBuilder, Response};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use crate::config::{HttpClientConfig, Provider};
+use crate::errors::TimeoutError;
+
+/// Settings, connect timeout and, for mTLS token requests, the PEM certificate and key
+type ClientKey = (HttpClientConfig, Duration, Option<(String, String)>);
+
+static CLIENTS: LazyLock<Mutex<HashMap<ClientKey, Client>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Client builder with proxy, trusted CA and client certificate settings applied.
+pub fn builder(config: &HttpClientConfig) -> Result<ClientBuilder> {
+    let mut builder = Client::builder();
+
+    let no_proxy = config.no_proxy.as_deref().unwrap_or_default();
+    match config.proxy {
+        Some(ref url) => {
+            let proxy =
+                reqwest::Proxy::all(url).with_context(|| format!("Invalid proxy URL '{url}'"))?;
+            builder =
+                builder.proxy(proxy.no_proxy(reqwest::NoProxy::from_string(&no_proxy.join(","))));
+        }
+        // Also turns off proxies from the environment
+        None if no_proxy.iter().any(|host| host == "*") => builder = builder.no_proxy(),
+        None => {}
+    }
+
+    for path in config.ca_certs.iter().flatten() {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read CA certificates from {path}"))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid CA certificates in {path}"))?;
+        if certs.is_empty() {
+            return Err(anyhow::anyhow!("No certificates found in {path}"));
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => {
+            let cert = std::fs::read_to_string(cert)
+                .with_context(|| format!("Failed to read client certificate from {cert}"))?;
+            let key = std::fs::read_to_string(key)
+                .with_context(|| format!("Failed to read client key from {key}"))?;
+            let pem = format!("{}\n{}", cert.trim_end(), key.trim_end());
+            let identity = reqwest::Identity::from_pem(pem.as_bytes())
+                .context("Invalid client certificate or key")?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(anyhow::anyhow!(
+                "client_cert and client_key must be configured together"
+            ));
+        }
+    }
+
+    Ok(builder)
+}
+
+/// Shared client for the given settings, built on first use.
+pub fn client(config: &HttpClientConfig, connect_timeout: Duration) -> Result<Client> {
+    cached((config.clone(), connect_timeout, None), || {
+        builder(config)?
+            .connect_timeout(connect_timeout)
+            .build()
+            .context("Failed to build HTTP client")
+    })
+}
+
+/// Shared client that presents an inline PEM certificate (chain) and private key,
+/// as used by x509 service keys. Built once per identity.
+pub fn mtls_client(
+    config: &HttpClientConfig,
+    connect_timeout: Duration,
+    certificate: &str,
+    key: &str,
+) -> Result<Client> {
+    let identity = Some((certificate.to_string(), key.to_string()));
+    cached((config.clone(), connect_timeout, identity), || {
+        let pem = format!("{}\n{}", certificate.trim_end(), key.trim_end());
+        let identity = reqwest::Identity::from_pem(pem.as_bytes())
+            .context("Invalid client certificate or key for x509 authentication")?;
+        builder(config)?
+            .connect_timeout(connect_timeout)
+            .identity(identity)
+            .build()
+            .context("Failed to build mTLS client")
+    })
+}
+
+fn cached(key: ClientKey, build: impl FnOnce() -> Result<Client>) -> Result<Client> {
+    if let Some(client) = CLIENTS.lock().unwrap().get(&key) {
+        return Ok(client.clone());
+    }
+
+    let client = build()?;
+    Ok(CLIENTS.lock().unwrap().entry(key).or_insert(client).clone())
+}
+
+/// Shared client for a provider's UAA and AI Core calls.
+pub fn provider_client(provider: &Provider) -> Result<Client> {
+    client(&provider.http, provider.timeouts.resolve().connect)
+}
+
+/// Send a request, giving up if the response headers don't arrive within
+/// `first_byte`. Set a total timeout on the request itself where it applies.
+pub async fn send(request: RequestBuilder, first_byte: Duration) -> Result<Response> {
+    match tokio::time::timeout(first_byte, request.send()).await {
+        Ok(response) => Ok(response?),
+        Err(_) => Err(TimeoutError::FirstByte {
+            secs: first_byte.as_secs(),
+        }
+        .into()),
+    }
+}
+
+/// Whether a request failed because of a connect, first-byte or total timeout.
+pub fn is_timeout(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause.is::<TimeoutError>()
+            || cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(reqwest::Error::is_timeout)
+    })
+}
+
+/// Check that a client can be built from the settings, reading the certificate
+/// files again even if a cached client exists.
+pub fn check(config: &HttpClientConfig) -> Result<()> {
+    builder(config)?
+        .build()
+        .context("Failed to build HTTP client")?;
+    Ok(())
+}
+
+/// Drop all cached clients, so that the next requests read certificate files
+/// again. Requests in flight keep the client they started with.
+pub fn reset() {
+    CLIENTS.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TlsConfig;
+    use crate::tls::{TlsContext, TlsListener};
+    use axum::Router;
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, Issuer, KeyPair};
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Serve a fixed body on every path and return the base URL.
+    async fn serve_text(body: &'static str) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().fallback(move || async move { body });
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_proxy_and_no_proxy() {
+        let proxy = serve_text("proxy").await;
+        let upstream = serve_text("direct").await;
+        let url = format!("{upstream}/v2/lm/deployments");
+        let get = |config: HttpClientConfig| {
+            let url = url.clone();
+            async move {
+                let client = builder(&config).unwrap().build().unwrap();
+                client.get(url).send().await.unwrap().text().await.unwrap()
+            }
+        };
+
+        let proxied = HttpClientConfig {
+            proxy: Some(proxy),
+            ..Default::default()
+        };
+        assert_eq!(get(proxied.clone()).await, "proxy");
+
+        let bypassed = HttpClientConfig {
+            no_proxy: Some(vec!["127.0.0.1".to_string()]),
+            ..proxied
+        };
+        assert_eq!(get(bypassed).await, "direct");
+
+        let invalid = HttpClientConfig {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(check(&invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_private_ca_and_client_certificate() {
+        let dir = TempDir::new().unwrap();
+        let write = |name: &str, contents: String| {
+            let path = dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            path.to_string_lossy().to_string()
+        };
+
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, "corp root");
+        let ca_key = KeyPair::generate().unwrap();
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let issuer = Issuer::new(ca_params, ca_key);
+        let issue = |name: &str| {
+            let key = KeyPair::generate().unwrap();
+            let cert = CertificateParams::new(vec![name.to_string()])
+                .unwrap()
+                .signed_by(&key, &issuer)
+                .unwrap();
+            (cert.pem(), key.serialize_pem())
+        };
+
+        // Upstream with a certificate from the private CA that requires client certificates
+        let (server_cert, server_key) = issue("localhost");
+        let tls = TlsConfig {
+            cert: write("server.crt", server_cert),
+            key: write("server.key", server_key),
+            client_ca: Some(write("ca.pem", ca.pem())),
+            client_cert_required: true,
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let listener = TlsListener::new(listener, TlsContext::load(&tls).unwrap()).unwrap();
+        let app = Router::new().fallback(|| async { "secure" });
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let url = format!("https://localhost:{port}/");
+
+        let (client_cert, client_key) = issue("router");
+        let trusted = HttpClientConfig {
+            ca_certs: tls.client_ca.clone().map(|ca| vec![ca]),
+            client_cert: Some(write("client.crt", client_cert)),
+            client_key: Some(write("client.key", client_key)),
+            ..Default::default()
+        };
+        let response = client(&trusted, Duration::from_secs(5))
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "secure");
+
+        // Without the client certificate the upstream rejects the handshake
+        let without_cert = HttpClientConfig {
+            client_cert: None,
+            client_key: None,
+            ..trusted.clone()
+        };
+        assert!(
+            client(&without_cert, Duration::from_secs(5))
+                .unwrap()
+                .get(&url)
+                .send()
+                .await
+                .is_err()
+        );
+
+        // Without the private CA the upstream certificate is not trusted
+        let untrusted = HttpClientConfig {
+            ca_certs: None,
+            ..trusted.clone()
+        };
+        assert!(
+            client(&untrusted, Duration::from_secs(5))
+                .unwrap()
+                .get(&url)
+                .send()
+                .await
+                .is_err()
+        );
+
+        let missing_key = HttpClientConfig {
+            client_key: None,
+            ..trusted
+        };
+        assert!(check(&missing_key).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_first_byte_and_total_timeouts() {
+        use axum::body::Body;
+        use axum::routing::get;
+        use futures::stream;
+
+        let app = Router::new()
+            .route(
+                "/slow-headers",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "late"
+                }),
+            )
+            .route(
+                "/slow-body",
+                get(|| async {
+                    let chunks = stream::unfold(0, |sent| async move {
+                        if sent > 0 {
+                            tokio::time::sleep(Duration::from_secs(5)).await;
+                        }
+                        Some((Ok::<_, std::io::Error>(format!("chunk {sent}\n")), sent + 1))
+                    });
+                    Body::from_stream(chunks)
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client = client(&HttpClientConfig::default(), Duration::from_secs(5)).unwrap();
+        let error = send(
+            client.get(format!("http://{addr}/slow-headers")),
+            Duration::from_millis(200),
+        )
+        .await
+        .unwrap_err();
+        assert!(is_timeout(&error));
+        assert!(error.to_string().contains("No response"), "{error:#}");
+
+        // Headers arrive in time, the body doesn't
+        let response = send(
+            client
+                .get(format!("http://{addr}/slow-body"))
+                .timeout(Duration::from_millis(500)),
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        let error = anyhow::Error::from(response.text().await.unwrap_err());
+        assert!(is_timeout(&error));
+
+        assert!(!is_timeout(&anyhow::anyhow!("connection refused")));
+    }
+
+    #[test]
+    fn test_mtls_client_is_built_once_per_identity() {
+        let key = KeyPair::generate().unwrap();
+        let cert = CertificateParams::new(vec!["router".to_string()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+        let (cert, key) = (cert.pem(), key.serialize_pem());
+        let config = HttpClientConfig::default();
+        let connect_timeout = Duration::from_millis(4321);
+
+        mtls_client(&config, connect_timeout, &cert, &key).unwrap();
+        let cache_key = (
+            config.clone(),
+            connect_timeout,
+            Some((cert.clone(), key.clone())),
+        );
+        assert!(CLIENTS.lock().unwrap().contains_key(&cache_key));
+
+        // A cached client is returned without parsing the identity again
+        cached(cache_key, || unreachable!()).unwrap();
+
+        assert!(mtls_client(&config, connect_timeout, "not a certificate", "not a key").is_err());
+    }
+}
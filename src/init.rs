@@ -0,0 +1,305 @@
+//! Bootstrap a config file from AI Core service keys (`acr config init`).
+
+use aes_gcm::aead::{OsRng, rand_core::RngCore};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::{
+    client::{AiCoreClient, Deployment},
+    config::{
+        ConfigFile, DiscoveryConfig, FallbackModels, HttpClientConfig, LoadBalancingStrategy,
+        Model, ProviderConfig, RegistrySnapshotConfig, RequestPolicy, TimeoutConfig,
+        without_vendor_prefix,
+    },
+    constants::{
+        config::DEFAULT_PORT,
+        deployment::RUNNING_STATUS,
+        models::{CLAUDE_PREFIX, GEMINI_PREFIX, GPT_PREFIX},
+    },
+    service_key::ServiceKey,
+    token::TokenManager,
+};
+
+/// Prefix of generated router API keys
+const API_KEY_PREFIX: &str = "acr-";
+
+/// Generate a random router API key.
+pub fn generate_api_key() -> String {
+    let mut bytes = [0u8; 24];
+    OsRng.fill_bytes(&mut bytes);
+    let key: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!("{API_KEY_PREFIX}{key}")
+}
+
+/// Provider entry referencing a service key file; the provider name is taken
+/// from the file name.
+pub fn provider_for_service_key(path: &Path, resource_group: &str) -> Result<ProviderConfig> {
+    // Fail early on files that aren't service keys
+    let service_key_file = path.to_string_lossy().to_string();
+    ServiceKey::from_file(&service_key_file)?;
+
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .filter(|stem| !stem.is_empty())
+        .with_context(|| format!("Invalid service key file name: {}", path.display()))?;
+
+    Ok(ProviderConfig {
+        name,
+        service_key_file: Some(service_key_file),
+        service_key_env: None,
+        uaa_token_url: None,
+        uaa_client_id: None,
+        uaa_client_secret: None,
+        certificate: None,
+        key: None,
+        genai_api_url: None,
+        resource_group: Some(resource_group.to_string()),
+        resource_groups: Vec::new(),
+        weight: 1,
+        enabled: true,
+        http: HttpClientConfig::default(),
+        timeouts: TimeoutConfig::default(),
+    })
+}
+
+/// Fetch the deployments of a provider in its resource group.
+pub async fn list_provider_deployments(provider: &ProviderConfig) -> Result<Vec<Deployment>> {
+    let provider = provider.clone().resolve()?;
+    let resource_group = provider.resource_group.clone();
+    let client = AiCoreClient::from_provider(provider, TokenManager::new());
+    let deployments = client.list_deployments(Some(&resource_group)).await?;
+    Ok(deployments.resources)
+}
+
+/// Build a complete config from providers and the deployments found on them.
+/// Every model with a running deployment becomes a `models` entry, and the
+/// most recently deployed model of each family becomes its fallback.
+pub fn build_config(
+    mut providers: Vec<ProviderConfig>,
+    deployments: &[Vec<Deployment>],
+    api_keys: Vec<String>,
+) -> ConfigFile {
+    // Provider names must be unique
+    let mut name_counts: HashMap<String, usize> = HashMap::new();
+    for provider in &mut providers {
+        let count = name_counts.entry(provider.name.clone()).or_default();
+        *count += 1;
+        if *count > 1 {
+            provider.name = format!("{}-{}", provider.name, count);
+        }
+    }
+
+    // AI Core model name -> newest start time across providers
+    let mut newest: HashMap<String, String> = HashMap::new();
+    for deployment in deployments.iter().flatten() {
+        if deployment.status != RUNNING_STATUS {
+            continue;
+        }
+        let Some(aicore_model_name) = deployment.get_aicore_model_name() else {
+            continue;
+        };
+        let started = deployment.started_at().to_string();
+        let entry = newest.entry(aicore_model_name).or_default();
+        if started > *entry {
+            *entry = started;
+        }
+    }
+
+    let mut aicore_model_names: Vec<&String> = newest.keys().collect();
+    aicore_model_names.sort();
+    let mut models: Vec<Model> = Vec::new();
+    for aicore_model_name in aicore_model_names {
+        let name = without_vendor_prefix(aicore_model_name);
+        if models.iter().any(|m| m.name == name) {
+            continue;
+        }
+        models.push(Model {
+            aicore_model_name: (name != *aicore_model_name).then(|| aicore_model_name.clone()),
+            name,
+            aicore_model_version: None,
+            configuration_name: None,
+            deployment_ids: BTreeMap::new(),
+            aliases: Vec::new(),
+            timeouts: TimeoutConfig::default(),
+            request: RequestPolicy::default(),
+        });
+    }
+
+    let fallback = |prefix: &str| {
+        models
+            .iter()
+            .filter(|m| m.name.starts_with(prefix) && !m.name.contains("embedding"))
+            .max_by_key(|m| {
+                let aicore_model_name = m.aicore_model_name.as_ref().unwrap_or(&m.name);
+                (newest.get(aicore_model_name), &m.name)
+            })
+            .map(|m| m.name.clone())
+    };
+    let fallback_models = FallbackModels {
+        claude: fallback(CLAUDE_PREFIX),
+        openai: fallback(GPT_PREFIX),
+        gemini: fallback(GEMINI_PREFIX),
+    };
+
+    ConfigFile {
+        log_level: Some("info".to_string()),
+        credentials: None,
+        providers,
+        port: DEFAULT_PORT,
+        models,
+        resource_group: None,
+        refresh_interval_secs: None,
+        refresh_timeout_secs: None,
+        fallback_models,
+        api_keys,
+        admin_api_keys: Vec::new(),
+        load_balancing: LoadBalancingStrategy::default(),
+        request_body_limit: None,
+        jwt_auth: None,
+        secret_key_file: None,
+        strict: false,
+        listen: Vec::new(),
+        http: HttpClientConfig::default(),
+        timeouts: TimeoutConfig::default(),
+        body_rules: Vec::new(),
+        alias_rewrites: Vec::new(),
+        discovery: DiscoveryConfig::default(),
+        registry_snapshot: RegistrySnapshotConfig::default(),
+    }
+}
+
+/// Render a config as YAML, leaving out unset and empty fields.
+pub fn to_yaml(config: &ConfigFile) -> Result<String> {
+    let mut document = serde_yaml::to_value(config).context("Failed to serialize config")?;
+    prune_empty(&mut document);
+    serde_yaml::to_string(&document).context("Failed to serialize config")
+}
+
+fn prune_empty(value: &mut serde_yaml::Value) {
+    use serde_yaml::Value;
+
+    match value {
+        Value::Mapping(mapping) => {
+            for (_, child) in mapping.iter_mut() {
+                prune_empty(child);
+            }
+            mapping.retain(|_, child| match child {
+                Value::Null => false,
+                Value::Sequence(items) => !items.is_empty(),
+                Value::Mapping(fields) => !fields.is_empty(),
+                _ => true,
+            });
+        }
+        Value::Sequence(items) => items.iter_mut().for_each(prune_empty),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const SERVICE_KEY: &str = r#"{
+        "serviceurls": {"AI_API_URL": "https://api.ai.example.com"},
+        "clientid": "sb-client",
+        "clientsecret": "s3cret",
+        "url": "https://tenant.authentication.eu10.hana.ondemand.com"
+    }"#;
+
+    fn deployment(id: &str, model: &str, status: &str, start_time: &str) -> Deployment {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "createdAt": "2025-01-01T00:00:00Z",
+            "modifiedAt": "2025-01-01T00:00:00Z",
+            "status": status,
+            "scenarioId": "foundation-models",
+            "configurationId": "config",
+            "startTime": start_time,
+            "details": {"resources": {"backendDetails": {"model": {"name": model, "version": "latest"}}}}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_generate_api_key() {
+        let key = generate_api_key();
+        assert!(key.starts_with(API_KEY_PREFIX));
+        assert_eq!(key.len(), API_KEY_PREFIX.len() + 48);
+        assert_ne!(key, generate_api_key());
+    }
+
+    #[test]
+    fn test_build_config_loads() {
+        let temp_dir = TempDir::new().unwrap();
+        let key_path = temp_dir.path().join("eu10.json");
+        fs::write(&key_path, SERVICE_KEY).unwrap();
+        let provider = provider_for_service_key(&key_path, "default").unwrap();
+        assert_eq!(provider.name, "eu10");
+
+        let deployments = vec![
+            vec![
+                deployment(
+                    "d1",
+                    "anthropic--claude-3.7-sonnet",
+                    "RUNNING",
+                    "2025-02-01",
+                ),
+                deployment("d2", "anthropic--claude-4-sonnet", "RUNNING", "2025-06-01"),
+                deployment("d3", "gpt-4o", "RUNNING", "2025-03-01"),
+                deployment("d4", "text-embedding-3-large", "RUNNING", "2025-07-01"),
+                deployment("d5", "gemini-2.5-pro", "STOPPED", "2025-08-01"),
+            ],
+            vec![deployment("d6", "gpt-4o", "RUNNING", "2025-04-01")],
+        ];
+        let config = build_config(
+            vec![provider.clone(), provider],
+            &deployments,
+            vec![generate_api_key()],
+        );
+
+        let names: Vec<&str> = config.providers.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["eu10", "eu10-2"]);
+        let models: Vec<(&str, Option<&str>)> = config
+            .models
+            .iter()
+            .map(|m| (m.name.as_str(), m.aicore_model_name.as_deref()))
+            .collect();
+        assert_eq!(
+            models,
+            vec![
+                ("claude-3.7-sonnet", Some("anthropic--claude-3.7-sonnet")),
+                ("claude-4-sonnet", Some("anthropic--claude-4-sonnet")),
+                ("gpt-4o", None),
+                ("text-embedding-3-large", None),
+            ]
+        );
+        assert_eq!(
+            config.fallback_models.claude.as_deref(),
+            Some("claude-4-sonnet")
+        );
+        assert_eq!(config.fallback_models.openai.as_deref(), Some("gpt-4o"));
+        assert_eq!(config.fallback_models.gemini, None);
+
+        let yaml = to_yaml(&config).unwrap();
+        assert!(!yaml.contains("null"));
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, yaml).unwrap();
+        let loaded = Config::load(Some(config_path.to_str().unwrap())).unwrap();
+        assert_eq!(loaded.providers.len(), 2);
+        assert_eq!(loaded.providers[0].uaa_client_secret, "s3cret");
+        assert_eq!(loaded.models.len(), 4);
+    }
+
+    #[test]
+    fn test_invalid_service_key_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let key_path = temp_dir.path().join("broken.json");
+        fs::write(&key_path, "{}").unwrap();
+        assert!(provider_for_service_key(&key_path, "default").is_err());
+    }
+}
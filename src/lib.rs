@@ -1,3 +1,6 @@
+pub mod admin;
+pub mod alias;
+pub mod auth;
 pub mod balancer;
 pub mod cli;
 pub mod client;
@@ -5,7 +8,17 @@ pub mod commands;
 pub mod config;
 pub mod constants;
 pub mod errors;
+pub mod http;
+pub mod init;
+pub mod listener;
+pub mod policy;
 pub mod proxy;
 pub mod registry;
+pub mod reload;
 pub mod routes;
+pub mod rules;
+pub mod secrets;
+pub mod service_key;
+pub mod tls;
 pub mod token;
+pub mod validate;
@@ -0,0 +1,263 @@
+//! Listening sockets: TCP on IPv4/IPv6 addresses (optionally with TLS) and
+//! Unix domain sockets.
+
+use anyhow::{Context, Result};
+use axum::Router;
+use axum::extract::connect_info::Connected;
+use axum::serve::IncomingStream;
+use futures::future::BoxFuture;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+
+use crate::config::TlsConfig;
+use crate::tls::{TlsContext, TlsListener};
+
+/// Prefix of Unix domain socket addresses
+const UNIX_PREFIX: &str = "unix:";
+
+/// Parsed form of a `listen` address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddress {
+    /// One or more TCP socket addresses (`localhost` binds both loopback addresses)
+    Tcp(Vec<SocketAddr>),
+    /// Path of a Unix domain socket
+    Unix(PathBuf),
+}
+
+impl ListenAddress {
+    pub fn parse(address: &str) -> Result<Self> {
+        if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+            if path.is_empty() {
+                return Err(anyhow::anyhow!("Unix socket path is empty: '{address}'"));
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        if let Some(port) = address.strip_prefix("localhost:") {
+            let port: u16 = port
+                .parse()
+                .with_context(|| format!("Invalid port in '{address}'"))?;
+            return Ok(Self::Tcp(vec![
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port),
+                SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), port),
+            ]));
+        }
+
+        let addr: SocketAddr = address.parse().with_context(|| {
+            format!(
+                "Invalid listen address '{address}', expected ip:port, [ipv6]:port, localhost:port or unix:/path"
+            )
+        })?;
+        Ok(Self::Tcp(vec![addr]))
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addrs) => {
+                let addrs: Vec<String> = addrs.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}", addrs.join(", "))
+            }
+            Self::Unix(path) => write!(f, "{UNIX_PREFIX}{}", path.display()),
+        }
+    }
+}
+
+/// Connection details available to handlers as `ConnectInfo<PeerInfo>`
+#[derive(Debug, Clone, Default)]
+pub struct PeerInfo {
+    /// Identity of the client certificate verified during the TLS handshake
+    pub client_identity: Option<String>,
+}
+
+impl Connected<IncomingStream<'_, tokio::net::TcpListener>> for PeerInfo {
+    fn connect_info(_: IncomingStream<'_, tokio::net::TcpListener>) -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(unix)]
+impl Connected<IncomingStream<'_, tokio::net::UnixListener>> for PeerInfo {
+    fn connect_info(_: IncomingStream<'_, tokio::net::UnixListener>) -> Self {
+        Self::default()
+    }
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for PeerInfo {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        Self {
+            client_identity: stream.remote_addr().client_identity.clone(),
+        }
+    }
+}
+
+/// A bound socket, ready to serve
+pub enum BoundListener {
+    Tcp(tokio::net::TcpListener),
+    Tls(TlsListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl BoundListener {
+    /// Serve `app` on this socket until the server stops.
+    pub fn serve(self, app: Router) -> BoxFuture<'static, Result<()>> {
+        let service = app.into_make_service_with_connect_info::<PeerInfo>();
+        match self {
+            Self::Tcp(listener) => {
+                Box::pin(
+                    async move { axum::serve(listener, service).await.context("Server error") },
+                )
+            }
+            Self::Tls(listener) => {
+                Box::pin(
+                    async move { axum::serve(listener, service).await.context("Server error") },
+                )
+            }
+            #[cfg(unix)]
+            Self::Unix(listener) => {
+                Box::pin(
+                    async move { axum::serve(listener, service).await.context("Server error") },
+                )
+            }
+        }
+    }
+}
+
+/// Bind all sockets of a listen address, terminating TLS on them if configured.
+pub async fn bind(address: &ListenAddress, tls: Option<&TlsConfig>) -> Result<Vec<BoundListener>> {
+    match address {
+        ListenAddress::Tcp(addrs) => {
+            let tls = tls.map(TlsContext::load).transpose()?;
+            let mut bound = Vec::new();
+            for addr in addrs {
+                match tokio::net::TcpListener::bind(addr).await {
+                    Ok(listener) => bound.push(match tls {
+                        Some(ref tls) => {
+                            BoundListener::Tls(TlsListener::new(listener, tls.clone())?)
+                        }
+                        None => BoundListener::Tcp(listener),
+                    }),
+                    // Hosts without IPv6 still get the IPv4 loopback for `localhost`
+                    Err(e) if addrs.len() > 1 && addr.is_ipv6() => {
+                        tracing::warn!("Skipping {}: {}", addr, e);
+                    }
+                    Err(e) => {
+                        return Err(e).with_context(|| format!("Failed to bind to {addr}"));
+                    }
+                }
+            }
+            Ok(bound)
+        }
+        #[cfg(unix)]
+        ListenAddress::Unix(path) => {
+            if tls.is_some() {
+                return Err(anyhow::anyhow!(
+                    "TLS is not supported on Unix sockets: {}",
+                    path.display()
+                ));
+            }
+            remove_stale_socket(path)?;
+            let listener = tokio::net::UnixListener::bind(path)
+                .with_context(|| format!("Failed to bind to Unix socket {}", path.display()))?;
+            Ok(vec![BoundListener::Unix(listener)])
+        }
+        #[cfg(not(unix))]
+        ListenAddress::Unix(path) => Err(anyhow::anyhow!(
+            "Unix sockets are not supported on this platform: {}",
+            path.display()
+        )),
+    }
+}
+
+/// Remove a socket file left behind by a previous run. Sockets another
+/// process still accepts connections on, and other files, are left alone.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixStream;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => match UnixStream::connect(path) {
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                std::fs::remove_file(path)
+                    .with_context(|| format!("Failed to remove stale socket {}", path.display()))
+            }
+            Ok(_) => Err(anyhow::anyhow!(
+                "Cannot listen on {}: address in use",
+                path.display()
+            )),
+            Err(e) => Err(anyhow::anyhow!(
+                "Cannot listen on {}: address in use ({e})",
+                path.display()
+            )),
+        },
+        Ok(_) => Err(anyhow::anyhow!(
+            "Cannot listen on {}: file exists and is not a socket",
+            path.display()
+        )),
+        Err(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listen_address() {
+        assert_eq!(
+            ListenAddress::parse("127.0.0.1:8900").unwrap(),
+            ListenAddress::Tcp(vec!["127.0.0.1:8900".parse().unwrap()])
+        );
+        assert_eq!(
+            ListenAddress::parse("[::]:8900").unwrap(),
+            ListenAddress::Tcp(vec!["[::]:8900".parse().unwrap()])
+        );
+        assert_eq!(
+            ListenAddress::parse("localhost:8901").unwrap(),
+            ListenAddress::Tcp(vec![
+                "127.0.0.1:8901".parse().unwrap(),
+                "[::1]:8901".parse().unwrap()
+            ])
+        );
+        assert_eq!(
+            ListenAddress::parse("unix:/run/acr.sock").unwrap(),
+            ListenAddress::Unix(PathBuf::from("/run/acr.sock"))
+        );
+
+        for invalid in ["8900", "example.com:8900", "localhost:http", "unix:", ""] {
+            assert!(ListenAddress::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bind_unix_socket_replaces_stale_socket() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("acr.sock");
+        let address = ListenAddress::Unix(path.clone());
+
+        let first = bind(&address, None).await.unwrap();
+        // A running instance keeps its socket
+        let Err(error) = bind(&address, None).await else {
+            panic!("bound a socket that is in use");
+        };
+        assert!(error.to_string().contains("address in use"), "{error}");
+
+        drop(first);
+        // The socket file is left behind and gets replaced
+        assert!(path.exists());
+        assert_eq!(bind(&address, None).await.unwrap().len(), 1);
+
+        let regular_file = temp_dir.path().join("config.yaml");
+        std::fs::write(&regular_file, "port: 8900").unwrap();
+        assert!(
+            bind(&ListenAddress::Unix(regular_file), None)
+                .await
+                .is_err()
+        );
+    }
+}
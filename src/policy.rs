@@ -0,0 +1,252 @@
+//! Per-model request policies: defaults, overrides, clamps, removed fields and
+//! a system prompt prefix, applied to request bodies before they are adapted
+//! to the model family.
+
+use serde_json::{Map, Value, json};
+
+use crate::config::{Bounds, RequestPolicy};
+use crate::proxy::LlmFamily;
+
+/// Apply a policy to a request body. Fields are removed first, then defaults,
+/// overrides and clamps are applied in that order.
+pub fn apply(policy: &RequestPolicy, body: &mut Value, family: &LlmFamily) {
+    let Some(obj) = body.as_object_mut() else {
+        return;
+    };
+
+    for field in &policy.remove {
+        remove_path(obj, field);
+    }
+    for (field, value) in &policy.defaults {
+        if get_path(obj, field).is_none_or(Value::is_null) {
+            set_path(obj, field, value.clone());
+        }
+    }
+    for (field, value) in &policy.overrides {
+        set_path(obj, field, value.clone());
+    }
+    for (field, bounds) in &policy.clamp {
+        if let Some(value) = get_path(obj, field)
+            && let Some(clamped) = clamp(value, bounds)
+        {
+            set_path(obj, field, clamped);
+        }
+    }
+    if let Some(ref prefix) = policy.system_prompt_prefix {
+        prepend_system_prompt(obj, family, prefix);
+    }
+}
+
+/// The nearest bound if `value` is a number outside `bounds`.
+fn clamp(value: &Value, bounds: &Bounds) -> Option<Value> {
+    let number = value.as_f64()?;
+    if let Some(ref min) = bounds.min
+        && min.as_f64().is_some_and(|min| number < min)
+    {
+        return Some(Value::Number(min.clone()));
+    }
+    if let Some(ref max) = bounds.max
+        && max.as_f64().is_some_and(|max| number > max)
+    {
+        return Some(Value::Number(max.clone()));
+    }
+    None
+}
+
+/// The value of a field given as a dotted path.
+pub fn get_path<'a>(obj: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+    let (parents, field) = split_path(path);
+    let mut current = obj;
+    for parent in parents {
+        current = current.get(parent)?.as_object()?;
+    }
+    current.get(field)
+}
+
+/// Set a field, creating missing parent objects. Parents that are not
+/// objects are left alone.
+pub fn set_path(obj: &mut Map<String, Value>, path: &str, value: Value) {
+    let (parents, field) = split_path(path);
+    let mut current = obj;
+    for parent in parents {
+        let child = current
+            .entry(parent.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        let Some(child) = child.as_object_mut() else {
+            return;
+        };
+        current = child;
+    }
+    current.insert(field.to_string(), value);
+}
+
+/// Remove a field, returning its value.
+pub fn remove_path(obj: &mut Map<String, Value>, path: &str) -> Option<Value> {
+    let (parents, field) = split_path(path);
+    let mut current = obj;
+    for parent in parents {
+        current = current.get_mut(parent)?.as_object_mut()?;
+    }
+    current.remove(field)
+}
+
+fn split_path(path: &str) -> (Vec<&str>, &str) {
+    let mut parts: Vec<&str> = path.split('.').collect();
+    let field = parts.pop().unwrap_or_default();
+    (parts, field)
+}
+
+fn prepend_system_prompt(obj: &mut Map<String, Value>, family: &LlmFamily, prefix: &str) {
+    match family {
+        LlmFamily::Claude => match obj.get_mut("system") {
+            Some(Value::String(system)) => *system = format!("{prefix}\n\n{system}"),
+            Some(Value::Array(blocks)) => blocks.insert(0, json!({"type": "text", "text": prefix})),
+            _ => {
+                obj.insert("system".to_string(), json!(prefix));
+            }
+        },
+        LlmFamily::OpenAi => {
+            // Embedding requests have no messages
+            let Some(messages) = obj.get_mut("messages").and_then(Value::as_array_mut) else {
+                return;
+            };
+            let first_is_system = messages.first().is_some_and(|message| {
+                matches!(
+                    message.get("role").and_then(Value::as_str),
+                    Some("system" | "developer")
+                )
+            });
+            let content = messages
+                .first_mut()
+                .filter(|_| first_is_system)
+                .and_then(|message| message.get_mut("content"));
+            match content {
+                Some(Value::String(content)) => *content = format!("{prefix}\n\n{content}"),
+                Some(Value::Array(parts)) => {
+                    parts.insert(0, json!({"type": "text", "text": prefix}))
+                }
+                _ => messages.insert(0, json!({"role": "system", "content": prefix})),
+            }
+        }
+        LlmFamily::Gemini => {
+            let part = json!({"text": prefix});
+            match obj
+                .get_mut("systemInstruction")
+                .and_then(|instruction| instruction.get_mut("parts"))
+                .and_then(Value::as_array_mut)
+            {
+                Some(parts) => parts.insert(0, part),
+                None => {
+                    obj.insert("systemInstruction".to_string(), json!({"parts": [part]}));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(yaml: &str) -> RequestPolicy {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_defaults_overrides_clamps_and_remove() {
+        let policy = policy(
+            r#"
+defaults: {max_tokens: 4096, temperature: 0.7}
+overrides: {top_k: 5}
+clamp:
+  max_tokens: {max: 8192}
+  temperature: {min: 0, max: 1}
+remove: [logit_bias, metadata.user_id]
+"#,
+        );
+
+        let mut body = json!({
+            "temperature": 1.5,
+            "top_k": 50,
+            "logit_bias": {"50256": -100},
+            "metadata": {"user_id": "u1", "trace": "t1"}
+        });
+        apply(&policy, &mut body, &LlmFamily::Claude);
+        assert_eq!(
+            body,
+            json!({
+                "max_tokens": 4096,
+                "temperature": 1,
+                "top_k": 5,
+                "metadata": {"trace": "t1"}
+            })
+        );
+
+        let mut body = json!({"max_tokens": 100000, "temperature": 0.2});
+        apply(&policy, &mut body, &LlmFamily::Claude);
+        assert_eq!(body["max_tokens"], json!(8192));
+        assert_eq!(body["temperature"], json!(0.2));
+    }
+
+    #[test]
+    fn test_nested_fields() {
+        let policy = policy(
+            "defaults: {generationConfig.maxOutputTokens: 2048}\nclamp: {generationConfig.temperature: {max: 1}}\n",
+        );
+
+        let mut body = json!({"contents": []});
+        apply(&policy, &mut body, &LlmFamily::Gemini);
+        assert_eq!(body["generationConfig"], json!({"maxOutputTokens": 2048}));
+
+        let mut body = json!({"generationConfig": {"maxOutputTokens": 10, "temperature": 2}});
+        apply(&policy, &mut body, &LlmFamily::Gemini);
+        assert_eq!(
+            body["generationConfig"],
+            json!({"maxOutputTokens": 10, "temperature": 1})
+        );
+    }
+
+    #[test]
+    fn test_system_prompt_prefix() {
+        let policy = policy("system_prompt_prefix: Be brief.\n");
+
+        let mut body = json!({"system": "You are helpful."});
+        apply(&policy, &mut body, &LlmFamily::Claude);
+        assert_eq!(body["system"], json!("Be brief.\n\nYou are helpful."));
+
+        let mut body = json!({"system": [{"type": "text", "text": "You are helpful."}]});
+        apply(&policy, &mut body, &LlmFamily::Claude);
+        assert_eq!(body["system"][0]["text"], json!("Be brief."));
+        assert_eq!(body["system"][1]["text"], json!("You are helpful."));
+
+        let mut body = json!({"messages": [{"role": "user", "content": "Hi"}]});
+        apply(&policy, &mut body, &LlmFamily::OpenAi);
+        assert_eq!(
+            body["messages"][0],
+            json!({"role": "system", "content": "Be brief."})
+        );
+
+        let mut body = json!({"messages": [
+            {"role": "developer", "content": "You are helpful."},
+            {"role": "user", "content": "Hi"}
+        ]});
+        apply(&policy, &mut body, &LlmFamily::OpenAi);
+        assert_eq!(
+            body["messages"][0]["content"],
+            json!("Be brief.\n\nYou are helpful.")
+        );
+        assert_eq!(body["messages"].as_array().unwrap().len(), 2);
+
+        let mut body = json!({"contents": []});
+        apply(&policy, &mut body, &LlmFamily::Gemini);
+        assert_eq!(
+            body["systemInstruction"],
+            json!({"parts": [{"text": "Be brief."}]})
+        );
+
+        // Embeddings have no system prompt
+        let mut body = json!({"input": "text"});
+        apply(&policy, &mut body, &LlmFamily::OpenAi);
+        assert_eq!(body, json!({"input": "text"}));
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use axum::{
     body::Body,
     http::{HeaderMap, HeaderValue, Method, StatusCode},
@@ -10,13 +10,20 @@ use serde_json::{Value, json};
 use std::time::Instant;
 use tokio_stream::wrappers::ReceiverStream;
 
+use crate::auth::{Authenticator, Principal};
 use crate::balancer::LoadBalancer;
-use crate::config::{Config, Provider};
+use crate::config::{BodyRule, Config, Provider, RequestPolicy, Timeouts};
 use crate::constants::{api::*, models::*};
-use crate::registry::ModelRegistry;
+use crate::errors::TimeoutError;
+use crate::http;
+use crate::policy;
+use crate::registry::{ModelRegistry, ResolvedBy, ResolvedDeployment};
 use crate::routes::AppError;
+use crate::rules;
 use crate::token::TokenManager;
 
+/// Extract the caller's credential from the request headers.
+/// The value may be a static API key or a JWT; `Authenticator` decides which.
 pub fn extract_api_key(headers: &axum::http::HeaderMap) -> Option<String> {
     headers
         .get("api-key")
@@ -82,18 +89,24 @@ pub struct ProxyRequest {
     pub model: String,          // Resolved/normalized model name
     pub original_model: String, // Original requested model name
     pub provider_name: String,  // Provider handling this request
+    pub deployment_id: String,
     pub resource_group: String,
+    pub principal: String, // Authenticated caller identity
+    pub timeouts: Timeouts,
 }
 
 /// Input parameters for building a ProxyRequest
 #[derive(Debug)]
 pub struct ProxyRequestParams<'a> {
     pub headers: &'a HeaderMap,
+    /// Identity of the verified TLS client certificate, if any
+    pub client_certificate: Option<&'a str>,
     pub method: Method,
     pub body: Value,
     pub model: String,
     pub action: Option<String>,
     pub config: &'a Config,
+    pub authenticator: &'a Authenticator,
     pub token_manager: &'a TokenManager,
     pub model_registry: &'a ModelRegistry,
     pub load_balancer: &'a LoadBalancer,
@@ -109,79 +122,125 @@ impl<'a> ProxyRequestBuilder<'a> {
         Self { params }
     }
 
-    /// Build a proxy request for a specific provider.
-    /// This is used for 429 fallback - try providers in order until one succeeds.
-    pub async fn build_for_provider(&self, provider: &Provider) -> Result<ProxyRequest, AppError> {
-        // Step 1: Extract and validate API key
-        let api_key = self.extract_api_key()?;
+    /// Authenticate the caller from the request headers.
+    /// The credential is either a static API key or, if configured, a JWT.
+    /// Without one, a client certificate verified by an mTLS listener is accepted.
+    pub async fn authenticate(&self) -> Result<Principal, AppError> {
+        match (self.extract_api_key(), self.params.client_certificate) {
+            (Ok(credential), _) => Ok(self.params.authenticator.authenticate(&credential).await?),
+            (Err(_), Some(identity)) => Ok(Principal::from_client_certificate(identity)),
+            (Err(e), None) => Err(e),
+        }
+    }
 
-        // Step 2: Get authentication token for this provider
-        let token = self.get_auth_token(&api_key, provider).await?;
+    /// Build proxy requests for a specific provider, one per deployment of the
+    /// model, in the order the load balancer wants them tried.
+    /// This is used for 429 fallback - try deployments and providers in order
+    /// until one succeeds.
+    pub async fn build_for_provider(
+        &self,
+        provider: &Provider,
+        principal: &Principal,
+    ) -> Result<Vec<ProxyRequest>, AppError> {
+        // Step 1: Get authentication token for this provider
+        let token = self.get_auth_token(provider).await?;
 
-        // Step 3: Resolve model and deployment for this provider
-        let (normalized_model, deployment_id) = self.resolve_model_for_provider(provider).await?;
+        // Step 2: Resolve model and deployments for this provider
+        let (normalized_model, deployments) = self.resolve_model_for_provider(provider).await?;
 
-        // Step 4: Determine LLM family and stream flag
+        // Step 3: Determine LLM family and stream flag
         let family = determine_family(&normalized_model);
         let stream = extract_stream_flag(&self.params.body, &family, &self.params.action);
 
-        // Step 5: Prepare request body
+        // Step 4: Prepare request body, applying the model's request policy
+        let model_config = self
+            .params
+            .model_registry
+            .find_model_config(&normalized_model);
         let mut body = self.params.body.clone();
-        prepare_body(&mut body, &family, stream, &normalized_model)?;
-
-        // Step 6: Build target URL using the provider's API URL
-        let url = build_url(
-            &normalized_model,
-            &deployment_id,
-            &self.params.action,
-            &provider.genai_api_url,
+        prepare_body(
+            &mut body,
             &family,
             stream,
+            &normalized_model,
+            model_config.map(|model| &model.request),
+            &self.params.config.body_rules,
         )?;
 
-        Ok(ProxyRequest {
-            family,
-            method: self.params.method.clone(),
-            body,
-            stream,
-            url,
-            token,
-            model: normalized_model,
-            original_model: self.params.model.clone(),
-            provider_name: provider.name.clone(),
-            resource_group: provider.resource_group.clone(),
-        })
+        // Step 5: Model timeouts take precedence over the provider's
+        let timeouts = match model_config {
+            Some(model) => provider.timeouts.merged(&model.timeouts).resolve(),
+            None => provider.timeouts.resolve(),
+        };
+
+        // Step 6: Build target URLs using the provider's API URL
+        deployments
+            .into_iter()
+            .map(|deployment| {
+                let url = build_url(
+                    &normalized_model,
+                    &deployment.deployment_id,
+                    &self.params.action,
+                    &provider.genai_api_url,
+                    &family,
+                    stream,
+                )?;
+                Ok(ProxyRequest {
+                    family: family.clone(),
+                    method: self.params.method.clone(),
+                    body: body.clone(),
+                    stream,
+                    url,
+                    token: token.clone(),
+                    model: normalized_model.clone(),
+                    original_model: self.params.model.clone(),
+                    provider_name: provider.name.clone(),
+                    deployment_id: deployment.deployment_id,
+                    resource_group: deployment.resource_group,
+                    principal: principal.subject.clone(),
+                    timeouts,
+                })
+            })
+            .collect()
     }
 
     fn extract_api_key(&self) -> Result<String, AppError> {
         extract_api_key(self.params.headers).ok_or(AppError::MissingApiKey)
     }
 
-    async fn get_auth_token(&self, api_key: &str, provider: &Provider) -> Result<String, AppError> {
+    async fn get_auth_token(&self, provider: &Provider) -> Result<String, AppError> {
         self.params
             .token_manager
-            .get_token_for_provider(api_key, provider)
+            .get_token(provider)
             .await
-            .map_err(AppError::Internal)?
-            .ok_or(AppError::InvalidApiKey)
+            .map_err(|source| AppError::ProviderToken {
+                provider: provider.name.clone(),
+                source,
+            })
     }
 
-    /// Resolve model to deployment ID for a specific provider
+    /// Resolve model to the balanced deployments for a specific provider
     async fn resolve_model_for_provider(
         &self,
         provider: &Provider,
-    ) -> Result<(String, String), AppError> {
+    ) -> Result<(String, Vec<ResolvedDeployment>), AppError> {
         let normalized_model = normalize_model(&self.params.model, self.params.model_registry)
+            .await
             .map_err(|e| AppError::BadRequest(e.to_string()))?;
 
-        // Try to get deployment for this specific provider
-        if let Some(deployment_id) = self
+        // Try to get deployments for this specific provider
+        let deployments = self
             .params
             .model_registry
-            .get_deployment_for_provider(&normalized_model, &provider.name)
-            .await
-        {
-            return Ok((normalized_model, deployment_id));
+            .get_deployments_for_provider(&normalized_model, &provider.name)
+            .await;
+        if !deployments.is_empty() {
+            let deployments = self.params.load_balancer.order_deployments(
+                &provider.name,
+                &normalized_model,
+                deployments,
+            );
+            return Ok((normalized_model, deployments));
         }
 
         // Model not available on this provider
@@ -198,6 +257,11 @@ pub enum ProxyExecuteResult {
     Response(Response),
     /// Got 429 rate limit - should try next provider
     RateLimited,
+    /// No (complete) response in time - should try next provider
+    TimedOut,
+    /// The deployment was deleted or stopped - should be evicted and the
+    /// next deployment tried
+    DeploymentUnavailable,
 }
 
 impl ProxyRequest {
@@ -221,13 +285,22 @@ impl ProxyRequest {
             serde_json::to_string_pretty(&self.body)?
         );
 
-        let response = client
+        let mut request = client
             .request(self.method.clone(), &self.url)
             .headers(headers)
-            .json(&self.body)
-            .send()
-            .await
-            .context("Failed to send proxy request")?;
+            .json(&self.body);
+        // Streams can legitimately run for a long time; they are bounded by the idle timeout
+        if !self.stream {
+            request = request.timeout(self.timeouts.total);
+        }
+        let response = match http::send(request, self.timeouts.first_byte).await {
+            Ok(response) => response,
+            Err(e) if http::is_timeout(&e) => {
+                self.log_timeout(&e, start_time);
+                return Ok(ProxyExecuteResult::TimedOut);
+            }
+            Err(e) => return Err(e.context("Failed to send proxy request")),
+        };
 
         if !response.status().is_success() {
             let elapsed = start_time.elapsed();
@@ -237,18 +310,35 @@ impl ProxyRequest {
             // Check for rate limiting - signal to try next provider
             if status == StatusCode::TOO_MANY_REQUESTS {
                 tracing::warn!(
-                    "Rate limited (429) on original_model: {}, resolved_model: {}, provider: {}, time: {:.2}ms",
+                    "Rate limited (429) on original_model: {}, resolved_model: {}, provider: {}, deployment: {}, time: {:.2}ms",
                     self.original_model,
                     self.model,
                     self.provider_name,
+                    self.deployment_id,
                     elapsed.as_secs_f64() * 1000.0
                 );
                 return Ok(ProxyExecuteResult::RateLimited);
             }
 
+            // Deleted or stopped since the last refresh - signal to evict it
+            if is_deployment_unavailable(status, &text) {
+                tracing::warn!(
+                    "Deployment unavailable ({}) on original_model: {}, resolved_model: {}, provider: {}, deployment: {}, time: {:.2}ms: {}",
+                    status,
+                    self.original_model,
+                    self.model,
+                    self.provider_name,
+                    self.deployment_id,
+                    elapsed.as_secs_f64() * 1000.0,
+                    text
+                );
+                return Ok(ProxyExecuteResult::DeploymentUnavailable);
+            }
+
             tracing::error!("Proxy request failed: {} - {}", status, text);
             tracing::info!(
-                "Proxy done - original_model: {}, resolved_model: {}, provider: {}, time: {:.2}ms, status: {}, stream: {}",
+                "Proxy done - principal: {}, original_model: {}, resolved_model: {}, provider: {}, time: {:.2}ms, status: {}, stream: {}",
+                self.principal,
                 self.original_model,
                 self.model,
                 self.provider_name,
@@ -270,9 +360,16 @@ impl ProxyRequest {
             ))
         } else {
             let result = self.handle_regular_response(response).await;
+            if let Err(ref e) = result
+                && http::is_timeout(e)
+            {
+                self.log_timeout(e, start_time);
+                return Ok(ProxyExecuteResult::TimedOut);
+            }
             let elapsed = start_time.elapsed();
             tracing::info!(
-                "Proxy done - original_model: {}, resolved_model: {}, provider: {}, time: {:.2}ms, status: 200, stream: {}",
+                "Proxy done - principal: {}, original_model: {}, resolved_model: {}, provider: {}, time: {:.2}ms, status: 200, stream: {}",
+                self.principal,
                 self.original_model,
                 self.model,
                 self.provider_name,
@@ -283,6 +380,18 @@ impl ProxyRequest {
         }
     }
 
+    fn log_timeout(&self, error: &anyhow::Error, start_time: Instant) {
+        tracing::warn!(
+            "Timed out on original_model: {}, resolved_model: {}, provider: {}, deployment: {}, time: {:.2}ms: {:#}",
+            self.original_model,
+            self.model,
+            self.provider_name,
+            self.deployment_id,
+            start_time.elapsed().as_secs_f64() * 1000.0,
+            error
+        );
+    }
+
     async fn handle_regular_response(&self, response: reqwest::Response) -> Result<Response> {
         let content_type = response
             .headers()
@@ -310,19 +419,36 @@ impl ProxyRequest {
         start_time: Instant,
     ) -> Result<Response> {
         let (tx, rx) =
-            tokio::sync::mpsc::channel::<Result<axum::body::Bytes, reqwest::Error>>(1024);
+            tokio::sync::mpsc::channel::<Result<axum::body::Bytes, std::io::Error>>(1024);
         let is_claude = matches!(self.family, LlmFamily::Claude);
         let model = self.model.clone();
         let original_model = self.original_model.clone();
         let provider_name = self.provider_name.clone();
+        let principal = self.principal.clone();
         let family = self.family.clone();
+        let stream_idle = self.timeouts.stream_idle;
 
         tokio::spawn(async move {
             let mut stream = response.bytes_stream();
             let mut buffer = String::new();
             let mut token_stats = TokenStats::default();
 
-            while let Some(chunk_result) = stream.next().await {
+            loop {
+                let chunk_result = match tokio::time::timeout(stream_idle, stream.next()).await {
+                    Ok(Some(chunk_result)) => chunk_result,
+                    Ok(None) => break,
+                    Err(_) => {
+                        let e = TimeoutError::StreamIdle {
+                            secs: stream_idle.as_secs(),
+                        };
+                        tracing::error!("Stream error on provider '{}': {}", provider_name, e);
+                        // Ends the response body abnormally, so the client sees the stream was cut
+                        let _ = tx
+                            .send(Err(std::io::Error::new(std::io::ErrorKind::TimedOut, e)))
+                            .await;
+                        break;
+                    }
+                };
                 match chunk_result {
                     Ok(chunk) => {
                         if let Ok(chunk_str) = String::from_utf8(chunk.to_vec()) {
@@ -361,7 +487,7 @@ impl ProxyRequest {
                     }
                     Err(e) => {
                         tracing::error!("Stream error: {}", e);
-                        let _ = tx.send(Err(e)).await;
+                        let _ = tx.send(Err(std::io::Error::other(e))).await;
                         break;
                     }
                 }
@@ -370,7 +496,8 @@ impl ProxyRequest {
             // Log completion when streaming is done
             let elapsed = start_time.elapsed();
             tracing::info!(
-                "Proxy done - original_model: {}, resolved_model: {}, provider: {}, time: {:.2}ms, status: 200, stream: true, {}",
+                "Proxy done - principal: {}, original_model: {}, resolved_model: {}, provider: {}, time: {:.2}ms, status: 200, stream: true, {}",
+                principal,
                 original_model,
                 model,
                 provider_name,
@@ -391,50 +518,46 @@ impl ProxyRequest {
     }
 }
 
-fn normalize_model(model: &str, registry: &ModelRegistry) -> Result<String> {
-    // 1. Exact match - if the model exists in config, use it directly
-    if registry.find_model_config(model).is_some() {
-        return Ok(model.to_string());
-    }
+/// Whether an error response says that the deployment itself no longer
+/// exists or is not running, rather than rejecting the request. Only AI
+/// Core's structured error code counts: a bare 404 may come from a bad path
+/// in the request, and the message text may echo the request.
+fn is_deployment_unavailable(status: StatusCode, body: &str) -> bool {
+    use crate::constants::deployment::UNAVAILABLE_ERROR_CODES;
 
-    // 2. Alias pattern match - find by configured alias patterns
-    if let Some(matched_model) = registry.find_model_by_alias(model) {
-        tracing::debug!(
-            "Model '{}' matched alias pattern for configured model '{}'",
-            model,
-            matched_model.name
-        );
-        return Ok(matched_model.name.clone());
+    if !status.is_client_error() && status != StatusCode::SERVICE_UNAVAILABLE {
+        return false;
     }
-
-    // 3. Family fallback - determine family prefix and check for configured fallback
-    let prefix = if model.starts_with(CLAUDE_PREFIX) {
-        CLAUDE_PREFIX
-    } else if model.starts_with(GEMINI_PREFIX) {
-        GEMINI_PREFIX
-    } else if model.starts_with(GPT_PREFIX) {
-        GPT_PREFIX
-    } else if model.starts_with(TEXT_PREFIX) {
-        TEXT_PREFIX
-    } else {
-        // Unknown family, return as-is
-        return Ok(model.to_string());
+    let Ok(body) = serde_json::from_str::<Value>(body) else {
+        return false;
     };
+    body.pointer("/error/code")
+        .and_then(Value::as_str)
+        .is_some_and(|code| UNAVAILABLE_ERROR_CODES.contains(&code))
+}
 
-    // Try to get configured fallback for this family
-    if let Some(fallback_model) = registry.get_fallback_model(prefix)
-        && registry.find_model_config(fallback_model).is_some()
-    {
-        tracing::info!(
-            "Model '{}' not found, falling back to configured '{}' for {} family",
-            model,
-            fallback_model,
-            prefix
-        );
-        return Ok(fallback_model.to_string());
+async fn normalize_model(model: &str, registry: &ModelRegistry) -> Result<String> {
+    let resolution = registry.resolve_model(model).await;
+    match resolution.resolved_by {
+        ResolvedBy::Alias { ref rule } => {
+            tracing::debug!(
+                "Model '{}' resolved to '{}' by {}",
+                model,
+                resolution.model,
+                rule
+            );
+        }
+        ResolvedBy::Fallback { ref family } => {
+            tracing::info!(
+                "Model '{}' not found, falling back to configured '{}' for {} family",
+                model,
+                resolution.model,
+                family
+            );
+        }
+        ResolvedBy::Name | ResolvedBy::Discovered | ResolvedBy::Unchanged => {}
     }
-
-    Ok(model.to_string())
+    Ok(resolution.model)
 }
 
 fn determine_family(model: &str) -> LlmFamily {
@@ -461,18 +584,25 @@ fn extract_stream_flag(body: &Value, family: &LlmFamily, action: &Option<String>
     }
 }
 
-fn prepare_body(body: &mut Value, family: &LlmFamily, stream: bool, model: &str) -> Result<()> {
+fn prepare_body(
+    body: &mut Value,
+    family: &LlmFamily,
+    stream: bool,
+    model: &str,
+    policy: Option<&RequestPolicy>,
+    body_rules: &[BodyRule],
+) -> Result<()> {
+    if let Some(policy) = policy {
+        policy::apply(policy, body, family);
+    }
+    rules::apply(body_rules, body, model);
+
     match family {
         LlmFamily::Claude => {
             if let Some(obj) = body.as_object_mut() {
                 obj.insert("anthropic_version".to_string(), json!(ANTHROPIC_VERSION));
                 obj.remove("stream");
                 obj.remove("model");
-                obj.remove("context_management");
-
-                if obj.contains_key("thinking") && obj.contains_key("temperature") {
-                    obj.remove("temperature");
-                }
             }
         }
         LlmFamily::Gemini => {
@@ -483,14 +613,6 @@ fn prepare_body(body: &mut Value, family: &LlmFamily, stream: bool, model: &str)
         }
         LlmFamily::OpenAi => {
             if let Some(obj) = body.as_object_mut() {
-                // For GPT-5 models, replace max_tokens with max_completion_tokens and drop temperature
-                if model.starts_with("gpt-5") {
-                    if let Some(max_tokens) = obj.remove("max_tokens") {
-                        obj.insert("max_completion_tokens".to_string(), max_tokens);
-                    }
-                    obj.remove("temperature");
-                }
-
                 // Add stream_options to include usage stats for streaming requests
                 if stream {
                     match obj.get_mut("stream_options") {
@@ -597,3 +719,65 @@ fn build_url(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_deployment_unavailable() {
+        let cases = [
+            (
+                StatusCode::NOT_FOUND,
+                r#"{"error": {"code": "DeploymentNotFound"}}"#,
+                true,
+            ),
+            (
+                StatusCode::PRECONDITION_FAILED,
+                r#"{"error": {"code": "DeploymentNotRunning", "message": "Deployment is stopped"}}"#,
+                true,
+            ),
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                r#"{"error": {"code": "DeploymentStopped"}}"#,
+                true,
+            ),
+            // Unknown paths and validation errors echoing the request are not
+            // about the deployment
+            (StatusCode::NOT_FOUND, "", false),
+            (StatusCode::NOT_FOUND, "Deployment not found", false),
+            (
+                StatusCode::BAD_REQUEST,
+                "Invalid messages: 'deployment not found, stopped' is not a valid role",
+                false,
+            ),
+            (
+                StatusCode::BAD_REQUEST,
+                r#"{"error": {"code": "invalid_request_error", "message": "deployment does not exist"}}"#,
+                false,
+            ),
+            (
+                StatusCode::CONFLICT,
+                r#"{"error": {"message": "DeploymentNotFound"}}"#,
+                false,
+            ),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                r#"{"error": {"code": "DeploymentNotFound"}}"#,
+                false,
+            ),
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Service unavailable",
+                false,
+            ),
+        ];
+        for (status, body, expected) in cases {
+            assert_eq!(
+                is_deployment_unavailable(status, body),
+                expected,
+                "{status} {body}"
+            );
+        }
+    }
+}
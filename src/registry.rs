@@ -1,21 +1,113 @@
 //! Model registry that tracks deployments across multiple providers.
 
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 
-use crate::client::AiCoreClient;
-use crate::config::{FallbackModels, Model, Provider};
+use crate::alias::{AliasMatch, AliasResolver};
+use crate::client::{AiCoreClient, ProviderDeployments, RunningDeployment};
+use crate::config::{
+    Config, DiscoveryConfig, FallbackModels, Model, Provider, RegistrySnapshotConfig,
+};
+use crate::constants::deployment::REFRESH_DEBOUNCE_SECS;
 use crate::token::TokenManager;
 
-/// Resolved deployment information including which provider hosts it
-#[derive(Debug, Clone)]
+/// Resolved deployment information including which provider hosts it.
+/// A model has one entry per running deployment, so a provider may appear
+/// several times.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResolvedDeployment {
     pub deployment_id: String,
     pub provider_name: String,
+    /// Resource group the deployment runs in, sent as `ai-resource-group`
+    pub resource_group: String,
+}
+
+/// How a requested model name was resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolvedBy {
+    /// The name of a configured model
+    Name,
+    /// The name of a model discovered from the running deployments
+    Discovered,
+    /// An alias or rewrite, described by `rule`
+    Alias { rule: String },
+    /// The configured fallback for the model family
+    Fallback { family: String },
+    /// Nothing matched; the name is used as requested
+    Unchanged,
+}
+
+/// Outcome of the deployment refreshes of a provider
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProviderRefreshStatus {
+    /// When the provider's deployments were last listed successfully
+    pub last_success: Option<SystemTime>,
+    /// When the latest refresh of the provider finished
+    pub last_attempt: Option<SystemTime>,
+    /// Why the latest refresh failed, if it did
+    pub last_error: Option<String>,
+    /// Whether some of the provider's deployments are kept from an earlier
+    /// refresh or loaded from the snapshot, rather than listed by the latest one
+    pub stale: bool,
+    /// When each resource group's deployments were last listed successfully;
+    /// `last_success` for groups missing here
+    pub group_success: HashMap<String, SystemTime>,
+}
+
+impl ProviderRefreshStatus {
+    /// How long ago the deployments of a resource group were last listed
+    fn group_age(&self, group: &str) -> Option<Duration> {
+        self.group_success
+            .get(group)
+            .copied()
+            .or(self.last_success)
+            .and_then(|success| success.elapsed().ok())
+    }
+
+    /// When the deployments of a resource group were last listed
+    fn group_listed_at(&self, group: &str) -> Option<SystemTime> {
+        self.group_success.get(group).copied().or(self.last_success)
+    }
+}
+
+/// Deployments of a provider listed by one refresh
+struct ProviderResolution {
+    models: HashMap<String, Vec<ResolvedDeployment>>,
+    listed_groups: Vec<String>,
+    /// Resource groups that could not be listed, with the reason
+    failed_groups: Vec<(String, String)>,
+}
+
+/// Contents of the registry snapshot file
+#[derive(Debug, Serialize, Deserialize)]
+struct RegistrySnapshot {
+    /// Unix time the snapshot was written
+    saved_at: u64,
+    /// Unix time each provider's deployments were last listed; `saved_at`
+    /// for providers missing here
+    #[serde(default)]
+    refreshed_at: HashMap<String, u64>,
+    models: HashMap<String, Vec<ResolvedDeployment>>,
+}
+
+/// The usable part of the registry snapshot file
+struct LoadedSnapshot {
+    refreshed_at: HashMap<String, SystemTime>,
+    models: HashMap<String, Vec<ResolvedDeployment>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelResolution {
+    pub model: String,
+    pub resolved_by: ResolvedBy,
 }
 
 /// Runtime model registry that manages resolved deployment IDs across multiple providers
@@ -25,6 +117,16 @@ pub struct ModelRegistry {
     resolved_models: Arc<RwLock<HashMap<String, Vec<ResolvedDeployment>>>>,
     /// Original model configurations from config file
     config_models: Vec<Model>,
+    /// Compiled model aliases and rewrites
+    aliases: AliasResolver,
+    /// Exposure of running deployments that no configured model covers
+    discovery: DiscoveryConfig,
+    /// Where the mapping is persisted and how long it may be used
+    snapshot: RegistrySnapshotConfig,
+    /// Refresh outcome per provider name
+    provider_status: Arc<RwLock<HashMap<String, ProviderRefreshStatus>>>,
+    /// Set once a refresh has listed the deployments of any provider
+    refreshed: Arc<AtomicBool>,
     /// Fallback models configuration for each family
     fallback_models: FallbackModels,
     /// Providers to query for deployments
@@ -33,6 +135,12 @@ pub struct ModelRegistry {
     token_manager: TokenManager,
     /// Refresh interval for background updates
     refresh_interval: Duration,
+    /// Time limit for refreshing a single provider
+    refresh_timeout: Duration,
+    /// When a refresh after a deployment error was last started, per provider
+    refresh_requested: Arc<Mutex<HashMap<String, Instant>>>,
+    /// Set when a config reload replaced this registry
+    stopped: Arc<AtomicBool>,
 }
 
 impl ModelRegistry {
@@ -44,14 +152,196 @@ impl ModelRegistry {
         token_manager: TokenManager,
         refresh_interval_secs: u64,
     ) -> Self {
+        // Patterns are checked when the configuration is loaded
+        let aliases = AliasResolver::new(&config_models, &[]).unwrap_or_else(|e| {
+            error!("Ignoring model aliases: {:#}", e);
+            AliasResolver::default()
+        });
         Self {
             resolved_models: Arc::new(RwLock::new(HashMap::new())),
             config_models,
+            aliases,
+            discovery: DiscoveryConfig::default(),
+            snapshot: RegistrySnapshotConfig::default(),
+            provider_status: Arc::new(RwLock::new(HashMap::new())),
+            refreshed: Arc::new(AtomicBool::new(false)),
             fallback_models,
             providers,
             token_manager,
             refresh_interval: Duration::from_secs(refresh_interval_secs),
+            refresh_timeout: Duration::from_secs(
+                crate::constants::config::DEFAULT_REFRESH_TIMEOUT_SECS,
+            ),
+            refresh_requested: Arc::new(Mutex::new(HashMap::new())),
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Create a registry for the models, aliases and providers of a configuration
+    pub fn from_config(config: &Config, token_manager: TokenManager) -> Self {
+        let mut registry = Self::new(
+            config.models.clone(),
+            config.fallback_models.clone(),
+            config.providers.clone(),
+            token_manager,
+            config.refresh_interval_secs,
+        );
+        match AliasResolver::new(&config.models, &config.alias_rewrites) {
+            Ok(aliases) => registry.aliases = aliases,
+            Err(e) => error!("Ignoring alias rewrites: {:#}", e),
+        }
+        registry.discovery = config.discovery.clone();
+        registry.snapshot = config.registry_snapshot.clone();
+        registry.refresh_timeout = Duration::from_secs(config.refresh_timeout_secs);
+        registry
+    }
+
+    /// Carry over resolved deployments from a registry being replaced, so that
+    /// requests keep routing until this registry has refreshed. Mappings for
+    /// models or providers that are no longer configured are dropped.
+    pub async fn seed_from(&self, previous: &ModelRegistry) {
+        let seeded = self.retain_configured(previous.resolved_models.read().await.clone());
+        *self.resolved_models.write().await = seeded;
+        *self.provider_status.write().await = previous.provider_status.read().await.clone();
+        self.refreshed
+            .store(previous.has_refreshed(), Ordering::SeqCst);
+    }
+
+    /// Drop mappings for models or providers that are not configured.
+    fn retain_configured(
+        &self,
+        resolved: HashMap<String, Vec<ResolvedDeployment>>,
+    ) -> HashMap<String, Vec<ResolvedDeployment>> {
+        resolved
+            .into_iter()
+            .filter(|(model, _)| {
+                self.find_model_config(model).is_some()
+                    || (self.discovery.enabled && self.discovery.exposed_name(model).is_some())
+            })
+            .map(|(model, mut deployments)| {
+                deployments.retain(|d| {
+                    self.providers
+                        .iter()
+                        .any(|p| p.enabled && p.name == d.provider_name)
+                });
+                (model, deployments)
+            })
+            .filter(|(_, deployments)| !deployments.is_empty())
+            .collect()
+    }
+
+    /// Load the snapshot file, unless it is missing or unreadable. Returns
+    /// the mapping without the deployments of providers last refreshed longer
+    /// ago than the staleness limit, and when each remaining provider was.
+    fn load_snapshot(&self) -> Option<LoadedSnapshot> {
+        let path = self.snapshot.path.as_ref()?;
+        if !Path::new(path).exists() {
+            return None;
+        }
+        let snapshot = std::fs::read_to_string(path)
+            .context("Failed to read file")
+            .and_then(|content| {
+                serde_json::from_str::<RegistrySnapshot>(&content).context("Failed to parse file")
+            });
+        let snapshot = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                warn!("Ignoring registry snapshot '{}': {:#}", path, e);
+                return None;
+            }
+        };
+
+        let mut refreshed_at = HashMap::new();
+        for provider_name in snapshot.models.values().flatten().map(|d| &d.provider_name) {
+            if refreshed_at.contains_key(provider_name) {
+                continue;
+            }
+            let secs = snapshot
+                .refreshed_at
+                .get(provider_name)
+                .copied()
+                .unwrap_or(snapshot.saved_at);
+            let refreshed = UNIX_EPOCH + Duration::from_secs(secs);
+            let age = refreshed.elapsed().unwrap_or_default();
+            if age > self.snapshot.max_staleness() {
+                warn!(
+                    "Ignoring registry snapshot entries of provider '{}': {}s old, older than max_staleness_secs",
+                    provider_name,
+                    age.as_secs()
+                );
+                continue;
+            }
+            refreshed_at.insert(provider_name.clone(), refreshed);
+        }
+
+        let mut models = snapshot.models;
+        for deployments in models.values_mut() {
+            deployments.retain(|d| refreshed_at.contains_key(&d.provider_name));
         }
+        let models = self.retain_configured(models);
+        refreshed_at.retain(|provider_name, _| {
+            models
+                .values()
+                .flatten()
+                .any(|d| &d.provider_name == provider_name)
+        });
+        Some(LoadedSnapshot {
+            refreshed_at,
+            models,
+        })
+    }
+
+    /// Write the mapping to the snapshot file, replacing it atomically.
+    /// `refreshed_at` is when each provider's deployments were last listed.
+    fn save_snapshot(
+        &self,
+        models: &HashMap<String, Vec<ResolvedDeployment>>,
+        refreshed_at: &HashMap<String, SystemTime>,
+    ) {
+        let Some(ref path) = self.snapshot.path else {
+            return;
+        };
+        let unix_secs = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        };
+        let snapshot = RegistrySnapshot {
+            saved_at: unix_secs(SystemTime::now()),
+            refreshed_at: refreshed_at
+                .iter()
+                .map(|(name, time)| (name.clone(), unix_secs(*time)))
+                .collect(),
+            models: models.clone(),
+        };
+        let temp_path = format!("{path}.tmp");
+        let result = serde_json::to_string(&snapshot)
+            .context("Failed to serialize snapshot")
+            .and_then(|content| {
+                std::fs::write(&temp_path, content)
+                    .with_context(|| format!("Failed to write {temp_path}"))
+            })
+            .and_then(|()| std::fs::rename(&temp_path, path).context("Failed to replace file"));
+        if let Err(e) = result {
+            warn!("Failed to save registry snapshot '{}': {:#}", path, e);
+        }
+    }
+
+    /// Whether a refresh has succeeded for any provider, as opposed to the
+    /// mapping only coming from the snapshot file
+    pub fn has_refreshed(&self) -> bool {
+        self.refreshed.load(Ordering::SeqCst)
+    }
+
+    /// Refresh outcome of each provider that has been refreshed or loaded
+    /// from the snapshot
+    pub async fn provider_status(&self) -> HashMap<String, ProviderRefreshStatus> {
+        self.provider_status.read().await.clone()
+    }
+
+    /// Stop the background refresh task
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
     }
 
     /// Start the registry with initial resolution and background refresh
@@ -59,6 +349,32 @@ impl ModelRegistry {
         // Validate fallback models configuration
         self.validate_fallback_models();
 
+        // Route from the last snapshot until the first refresh succeeds
+        if self.resolved_models.read().await.is_empty()
+            && let Some(LoadedSnapshot {
+                refreshed_at,
+                models,
+            }) = self.load_snapshot()
+        {
+            info!(
+                "Loaded {} models of {} providers from registry snapshot",
+                models.len(),
+                refreshed_at.len()
+            );
+            *self.resolved_models.write().await = models;
+            *self.provider_status.write().await = refreshed_at
+                .into_iter()
+                .map(|(name, refreshed)| {
+                    let status = ProviderRefreshStatus {
+                        last_success: Some(refreshed),
+                        stale: true,
+                        ..Default::default()
+                    };
+                    (name, status)
+                })
+                .collect();
+        }
+
         // Initial resolution
         self.refresh_deployments().await?;
 
@@ -103,19 +419,23 @@ impl ModelRegistry {
         }
     }
 
-    /// Get deployment info for a model on a specific provider
-    pub async fn get_deployment_for_provider(
+    /// Get the deployments of a model on a specific provider
+    pub async fn get_deployments_for_provider(
         &self,
         model_name: &str,
         provider_name: &str,
-    ) -> Option<String> {
+    ) -> Vec<ResolvedDeployment> {
         let resolved = self.resolved_models.read().await;
-        resolved.get(model_name).and_then(|deployments| {
-            deployments
-                .iter()
-                .find(|d| d.provider_name == provider_name)
-                .map(|d| d.deployment_id.clone())
-        })
+        resolved
+            .get(model_name)
+            .map(|deployments| {
+                deployments
+                    .iter()
+                    .filter(|d| d.provider_name == provider_name)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 
     /// Get all providers that have a specific model deployed
@@ -133,6 +453,11 @@ impl ModelRegistry {
             .map(|d| d.deployment_id.clone())
     }
 
+    /// All resolved models with their deployments
+    pub async fn resolved_models(&self) -> HashMap<String, Vec<ResolvedDeployment>> {
+        self.resolved_models.read().await.clone()
+    }
+
     /// Get all available (resolved) model names
     pub async fn get_available_models(&self) -> Vec<String> {
         let resolved = self.resolved_models.read().await;
@@ -168,29 +493,60 @@ impl ModelRegistry {
         self.config_models.iter().map(|m| m.name.as_str()).collect()
     }
 
-    /// Find a model config by checking aliases with glob pattern matching.
-    /// Returns the model with the most specific matching pattern.
-    /// Specificity is determined by the length of the literal prefix before the `*`.
+    /// Find a model config by its aliases. Returns the model with the most
+    /// specific matching alias, see [`crate::alias`].
     pub fn find_model_by_alias(&self, requested_model: &str) -> Option<&Model> {
-        let mut best_match: Option<(&Model, usize)> = None;
-
-        for model in &self.config_models {
-            for alias in &model.aliases {
-                if let Some(specificity) = glob_matches(alias, requested_model) {
-                    match &best_match {
-                        None => {
-                            best_match = Some((model, specificity));
-                        }
-                        Some((_, current_specificity)) if specificity > *current_specificity => {
-                            best_match = Some((model, specificity));
-                        }
-                        _ => {} // Keep current best match
-                    }
-                }
-            }
+        let matched = self.aliases.resolve(requested_model)?;
+        self.find_model_config(&matched.target)
+    }
+
+    /// All aliases and rewrites matching a requested name, most specific first
+    pub fn alias_matches(&self, requested_model: &str) -> Vec<AliasMatch<'_>> {
+        self.aliases.matches(requested_model)
+    }
+
+    /// Resolve a requested model name: configured and discovered names first,
+    /// then aliases and rewrites, then the fallback model of the family.
+    pub async fn resolve_model(&self, requested_model: &str) -> ModelResolution {
+        use crate::constants::models::*;
+
+        let resolution = |model: &str, resolved_by| ModelResolution {
+            model: model.to_string(),
+            resolved_by,
+        };
+
+        if self.find_model_config(requested_model).is_some() {
+            return resolution(requested_model, ResolvedBy::Name);
+        }
+        if self.discovery.enabled && self.is_model_available(requested_model).await {
+            return resolution(requested_model, ResolvedBy::Discovered);
+        }
+
+        if let Some(matched) = self.aliases.resolve(requested_model) {
+            return resolution(
+                &matched.target,
+                ResolvedBy::Alias {
+                    rule: matched.rule.to_string(),
+                },
+            );
+        }
+
+        let family = [CLAUDE_PREFIX, GEMINI_PREFIX, GPT_PREFIX, TEXT_PREFIX]
+            .into_iter()
+            .find(|prefix| requested_model.starts_with(prefix));
+        if let Some(family) = family
+            && let Some(fallback_model) = self.get_fallback_model(family)
+            && self.find_model_config(fallback_model).is_some()
+        {
+            return resolution(
+                fallback_model,
+                ResolvedBy::Fallback {
+                    family: family.to_string(),
+                },
+            );
         }
 
-        best_match.map(|(model, _)| model)
+        resolution(requested_model, ResolvedBy::Unchanged)
     }
 
     async fn background_refresh(&self) {
@@ -201,111 +557,481 @@ impl ModelRegistry {
 
         loop {
             interval.tick().await;
+            if self.stopped.load(Ordering::SeqCst) {
+                info!("Background refresh stopped");
+                return;
+            }
             if let Err(e) = self.refresh_deployments().await {
                 error!("Failed to refresh deployments: {}", e);
             }
         }
     }
 
-    async fn refresh_deployments(&self) -> Result<()> {
+    /// Refresh the deployments of all enabled providers now
+    pub async fn refresh_deployments(&self) -> Result<()> {
+        self.refresh_providers(None).await
+    }
+
+    /// Refresh the deployments of all enabled providers, or only of the one
+    /// named `only`, keeping the other providers' deployments as they are.
+    async fn refresh_providers(&self, only: Option<&str>) -> Result<()> {
+        let providers: Vec<&Provider> = self.providers.iter().filter(|p| p.enabled).collect();
+        let targeted: Vec<&Provider> = providers
+            .iter()
+            .copied()
+            .filter(|p| only.is_none_or(|name| p.name == name))
+            .collect();
         info!(
             "Refreshing deployment mappings for {} providers...",
-            self.providers.len()
+            targeted.len()
         );
 
+        // A hanging provider must not hold up the others
+        let results = join_all(targeted.iter().map(|provider| async move {
+            tokio::time::timeout(self.refresh_timeout, self.resolve_provider(provider))
+                .await
+                .unwrap_or_else(|_| {
+                    Err(anyhow!(
+                        "timed out after {}s",
+                        self.refresh_timeout.as_secs()
+                    ))
+                })
+        }))
+        .await;
+        let mut results: HashMap<&str, Result<ProviderResolution>> = targeted
+            .iter()
+            .map(|p| p.name.as_str())
+            .zip(results)
+            .collect();
+
+        let mut resolved_models = self.resolved_models.write().await;
+        let mut provider_status = self.provider_status.write().await;
         let mut all_resolved: HashMap<String, Vec<ResolvedDeployment>> = HashMap::new();
+        let mut failed_providers = 0;
+        let now = SystemTime::now();
 
-        // Query each provider for deployments
-        for provider in &self.providers {
-            if !provider.enabled {
+        for provider in &providers {
+            let Some(result) = results.remove(provider.name.as_str()) else {
+                for (model, deployments) in provider_deployments(&resolved_models, &provider.name) {
+                    all_resolved.entry(model).or_default().extend(deployments);
+                }
                 continue;
-            }
-
-            info!(
-                "Querying provider '{}' (resource_group: {})...",
-                provider.name, provider.resource_group
-            );
-
-            // Create a client for this provider
-            let client = AiCoreClient::from_provider(provider.clone(), self.token_manager.clone());
-
-            match client
-                .build_model_to_deployment_mapping(Some(&provider.resource_group))
-                .await
-            {
-                Ok(aicore_deployments) => {
-                    for model_config in &self.config_models {
-                        // Use aicore_model_name if specified, otherwise use the model name itself
-                        let aicore_model_name = model_config
-                            .aicore_model_name
-                            .as_ref()
-                            .unwrap_or(&model_config.name);
-
-                        // Resolve from AI Core model name
-                        if let Some(deployment_id) = aicore_deployments.get(aicore_model_name) {
-                            all_resolved
-                                .entry(model_config.name.clone())
-                                .or_default()
-                                .push(ResolvedDeployment {
-                                    deployment_id: deployment_id.clone(),
-                                    provider_name: provider.name.clone(),
-                                });
-                            info!(
-                                "Provider '{}': Model '{}' -> aicore_model_name: '{}' -> deployment_id: {}",
-                                provider.name, model_config.name, aicore_model_name, deployment_id
-                            );
+            };
+            let status = provider_status.entry(provider.name.clone()).or_default();
+            status.last_attempt = Some(now);
+            // Keep the previous deployments of the resource groups that could
+            // not be listed, until they become too old
+            let max_staleness = self.snapshot.max_staleness();
+            let previous = provider_deployments(&resolved_models, &provider.name);
+            let kept = |status: &ProviderRefreshStatus, failed: &dyn Fn(&str) -> bool| {
+                let mut kept = previous.clone();
+                kept.retain(|_, deployments| {
+                    deployments.retain(|d| {
+                        failed(&d.resource_group)
+                            && status
+                                .group_age(&d.resource_group)
+                                .is_some_and(|age| age <= max_staleness)
+                    });
+                    !deployments.is_empty()
+                });
+                kept
+            };
+            let resolved = match result {
+                Ok(resolution) if resolution.failed_groups.is_empty() => {
+                    status.last_success = Some(now);
+                    status.last_error = None;
+                    status.stale = false;
+                    status.group_success = resolution
+                        .listed_groups
+                        .into_iter()
+                        .map(|group| (group, now))
+                        .collect();
+                    resolution.models
+                }
+                Ok(mut resolution) => {
+                    let failed: Vec<&str> = resolution
+                        .failed_groups
+                        .iter()
+                        .map(|(group, _)| group.as_str())
+                        .collect();
+                    let kept = kept(status, &|group| failed.contains(&group));
+                    warn!(
+                        "Provider '{}': failed to list resource group(s) {}. Keeping {} of their models from earlier refreshes.",
+                        provider.name,
+                        failed.join(", "),
+                        kept.len()
+                    );
+                    status.last_error = Some(
+                        resolution
+                            .failed_groups
+                            .iter()
+                            .map(|(group, e)| format!("resource group '{group}': {e}"))
+                            .collect::<Vec<_>>()
+                            .join("; "),
+                    );
+                    status.stale = !kept.is_empty();
+                    // Groups that are gone for good are forgotten with their
+                    // deployments
+                    status.group_success.retain(|group, _| {
+                        kept.values().flatten().any(|d| &d.resource_group == group)
+                    });
+                    if let Some(last_success) = status.last_success {
+                        for group in &failed {
+                            if kept.values().flatten().any(|d| &d.resource_group == group) {
+                                status
+                                    .group_success
+                                    .entry(group.to_string())
+                                    .or_insert(last_success);
+                            }
                         }
                     }
+                    for group in resolution.listed_groups {
+                        status.group_success.insert(group, now);
+                    }
+                    status.last_success = Some(now);
+                    for (model, deployments) in kept {
+                        resolution
+                            .models
+                            .entry(model)
+                            .or_default()
+                            .extend(deployments);
+                    }
+                    resolution.models
                 }
                 Err(e) => {
-                    error!(
-                        "Failed to query provider '{}': {}. Skipping this provider.",
-                        provider.name, e
-                    );
+                    failed_providers += 1;
+                    status.last_error = Some(format!("{e:#}"));
+                    let kept = kept(status, &|_| true);
+                    status.stale = !kept.is_empty();
+                    if status.stale {
+                        error!(
+                            "Failed to query provider '{}': {:#}. Keeping its deployments from earlier refreshes.",
+                            provider.name, e
+                        );
+                    } else {
+                        error!(
+                            "Failed to query provider '{}': {:#}. Skipping this provider.",
+                            provider.name, e
+                        );
+                    }
+                    kept
                 }
+            };
+            for (model, deployments) in resolved {
+                all_resolved.entry(model).or_default().extend(deployments);
             }
         }
 
         let resolved_count = all_resolved.len();
         let total_deployments: usize = all_resolved.values().map(|v| v.len()).sum();
 
+        let refreshed_at: Option<HashMap<String, SystemTime>> = (failed_providers < targeted.len())
+            .then(|| {
+                // The oldest listing among each provider's deployments
+                provider_status
+                    .iter()
+                    .filter_map(|(name, status)| {
+                        let oldest = all_resolved
+                            .values()
+                            .flatten()
+                            .filter(|d| &d.provider_name == name)
+                            .filter_map(|d| status.group_listed_at(&d.resource_group))
+                            .min()?;
+                        Some((name.clone(), oldest))
+                    })
+                    .collect()
+            });
+
         // Update the resolved models
-        {
-            let mut resolved_models = self.resolved_models.write().await;
-            *resolved_models = all_resolved;
+        *resolved_models = all_resolved;
+        drop(provider_status);
+        let resolved_models = resolved_models.downgrade();
+        if let Some(refreshed_at) = refreshed_at {
+            self.refreshed.store(true, Ordering::SeqCst);
+            self.save_snapshot(&resolved_models, &refreshed_at);
         }
 
         info!(
-            "Deployment refresh complete: {} models resolved across {} provider deployments",
-            resolved_count, total_deployments
+            "Deployment refresh complete: {} models resolved across {} provider deployments ({} providers failed)",
+            resolved_count, total_deployments, failed_providers
         );
 
         Ok(())
     }
-}
 
-/// Check if a glob pattern matches a string.
-/// Only supports trailing `*` wildcard (prefix matching).
-/// Returns the specificity (length of literal prefix) if matches, None otherwise.
-/// Higher specificity = more specific match.
-fn glob_matches(pattern: &str, input: &str) -> Option<usize> {
-    if let Some(prefix) = pattern.strip_suffix('*') {
-        // Pattern ends with *: prefix match
-        if input.starts_with(prefix) {
-            Some(prefix.len()) // Specificity = length of literal part
-        } else {
-            None
+    /// Remove a deployment that AI Core reported as gone or not running, so
+    /// that requests stop going to it before the next refresh.
+    pub async fn evict_deployment(&self, provider_name: &str, deployment_id: &str) {
+        let mut resolved_models = self.resolved_models.write().await;
+        let mut evicted = Vec::new();
+        resolved_models.retain(|model, deployments| {
+            let before = deployments.len();
+            deployments
+                .retain(|d| d.provider_name != provider_name || d.deployment_id != deployment_id);
+            if deployments.len() < before {
+                evicted.push(model.clone());
+            }
+            !deployments.is_empty()
+        });
+        if !evicted.is_empty() {
+            evicted.sort();
+            warn!(
+                "Provider '{}': Evicted deployment '{}' of model(s) {}",
+                provider_name,
+                deployment_id,
+                evicted.join(", ")
+            );
         }
-    } else {
-        // No wildcard: exact match only
-        if pattern == input {
-            Some(pattern.len())
-        } else {
-            None
+    }
+
+    /// Refresh a provider's deployments in the background, unless it was
+    /// requested less than the debounce period ago, so that a burst of
+    /// failing requests triggers a single refresh.
+    pub fn request_provider_refresh(&self, provider_name: &str) {
+        let debounce = Duration::from_secs(REFRESH_DEBOUNCE_SECS);
+        {
+            let mut requested = self.refresh_requested.lock().unwrap();
+            if requested
+                .get(provider_name)
+                .is_some_and(|at| at.elapsed() < debounce)
+            {
+                return;
+            }
+            requested.insert(provider_name.to_string(), Instant::now());
+        }
+
+        info!(
+            "Provider '{}': Refreshing deployments after a deployment error",
+            provider_name
+        );
+        let registry = self.clone();
+        let provider_name = provider_name.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = registry.refresh_providers(Some(&provider_name)).await {
+                error!(
+                    "Failed to refresh deployments of provider '{}': {}",
+                    provider_name, e
+                );
+            }
+        });
+    }
+
+    /// List a provider's running deployments and map them to configured and
+    /// discovered models.
+    async fn resolve_provider(&self, provider: &Provider) -> Result<ProviderResolution> {
+        info!(
+            "Querying provider '{}' (resource_groups: {})...",
+            provider.name,
+            provider.resource_group_patterns().join(", ")
+        );
+
+        // Create a client for this provider
+        let client = AiCoreClient::from_provider(provider.clone(), self.token_manager.clone());
+        let ProviderDeployments {
+            models: aicore_deployments,
+            listed_groups,
+            failed_groups,
+        } = client.build_provider_deployment_mapping().await?;
+
+        let mut resolved: HashMap<String, Vec<ResolvedDeployment>> = HashMap::new();
+        for model_config in &self.config_models {
+            let selected = select_deployments(model_config, &provider.name, &aicore_deployments);
+            if selected.is_empty() {
+                if let Some(deployment_id) = model_config.deployment_ids.get(&provider.name) {
+                    warn!(
+                        "Provider '{}': Deployment '{}' of model '{}' is not running",
+                        provider.name, deployment_id, model_config.name
+                    );
+                }
+                continue;
+            }
+            add_resolved(&mut resolved, &model_config.name, provider, &selected);
+            info!(
+                "Provider '{}': Model '{}' -> {}",
+                provider.name,
+                model_config.name,
+                describe(&selected)
+            );
+        }
+        if self.discovery.enabled {
+            self.add_discovered(provider, &aicore_deployments, &mut resolved);
+        }
+        Ok(ProviderResolution {
+            models: resolved,
+            listed_groups,
+            failed_groups: failed_groups
+                .into_iter()
+                .map(|(group, e)| (group, format!("{e:#}")))
+                .collect(),
+        })
+    }
+
+    /// Register the running deployments that no configured model claims,
+    /// under the names the discovery settings expose them as.
+    fn add_discovered(
+        &self,
+        provider: &Provider,
+        aicore_deployments: &HashMap<String, Vec<RunningDeployment>>,
+        all_resolved: &mut HashMap<String, Vec<ResolvedDeployment>>,
+    ) {
+        let mut aicore_model_names: Vec<&String> = aicore_deployments.keys().collect();
+        aicore_model_names.sort();
+        for aicore_model_name in aicore_model_names {
+            let configured = self
+                .config_models
+                .iter()
+                .any(|m| m.aicore_model_name.as_ref().unwrap_or(&m.name) == aicore_model_name);
+            if configured {
+                continue;
+            }
+            let Some(name) = self.discovery.exposed_name(aicore_model_name) else {
+                continue;
+            };
+            // A configured model of the same name wins even if it maps elsewhere
+            if self.find_model_config(&name).is_some() {
+                continue;
+            }
+
+            if all_resolved.get(&name).is_some_and(|deployments| {
+                deployments.iter().any(|d| d.provider_name == provider.name)
+            }) {
+                continue;
+            }
+            let selected = newest_version(aicore_deployments[aicore_model_name].iter().collect());
+            add_resolved(all_resolved, &name, provider, &selected);
+            info!(
+                "Provider '{}': Discovered model '{}' -> aicore_model_name: '{}', {}",
+                provider.name,
+                name,
+                aicore_model_name,
+                describe(&selected)
+            );
         }
     }
 }
 
+/// The running deployments on a provider that serve a configured model.
+///
+/// A deployment id pinned for the provider is used as is. Otherwise the
+/// deployments of the model's AI Core name are filtered by the configured
+/// version and configuration name; without a version, only the newest version
+/// running is used.
+pub fn select_deployments<'a>(
+    model: &Model,
+    provider_name: &str,
+    aicore_deployments: &'a HashMap<String, Vec<RunningDeployment>>,
+) -> Vec<&'a RunningDeployment> {
+    if let Some(deployment_id) = model.deployment_ids.get(provider_name) {
+        return aicore_deployments
+            .values()
+            .flatten()
+            .filter(|d| &d.id == deployment_id)
+            .collect();
+    }
+
+    // Use aicore_model_name if specified, otherwise use the model name itself
+    let aicore_model_name = model.aicore_model_name.as_ref().unwrap_or(&model.name);
+    let candidates: Vec<&RunningDeployment> = aicore_deployments
+        .get(aicore_model_name)
+        .into_iter()
+        .flatten()
+        .filter(|d| {
+            model
+                .configuration_name
+                .as_ref()
+                .is_none_or(|name| d.configuration_name.as_ref() == Some(name))
+        })
+        .collect();
+    match model.aicore_model_version {
+        Some(ref version) => candidates
+            .into_iter()
+            .filter(|d| d.model_version.as_ref() == Some(version))
+            .collect(),
+        None => newest_version(candidates),
+    }
+}
+
+/// The deployments of the version that started most recently, in their
+/// original order.
+pub fn newest_version(deployments: Vec<&RunningDeployment>) -> Vec<&RunningDeployment> {
+    let Some(newest) = deployments.iter().max_by_key(|d| &d.started_at) else {
+        return Vec::new();
+    };
+    let version = newest.model_version.clone();
+    deployments
+        .into_iter()
+        .filter(|d| d.model_version == version)
+        .collect()
+}
+
+/// The deployments of one provider in a mapping
+fn provider_deployments(
+    resolved: &HashMap<String, Vec<ResolvedDeployment>>,
+    provider_name: &str,
+) -> HashMap<String, Vec<ResolvedDeployment>> {
+    resolved
+        .iter()
+        .map(|(model, deployments)| {
+            let deployments: Vec<ResolvedDeployment> = deployments
+                .iter()
+                .filter(|d| d.provider_name == provider_name)
+                .cloned()
+                .collect();
+            (model.clone(), deployments)
+        })
+        .filter(|(_, deployments)| !deployments.is_empty())
+        .collect()
+}
+
+fn add_resolved(
+    all_resolved: &mut HashMap<String, Vec<ResolvedDeployment>>,
+    name: &str,
+    provider: &Provider,
+    deployments: &[&RunningDeployment],
+) {
+    all_resolved
+        .entry(name.to_string())
+        .or_default()
+        .extend(deployments.iter().map(|d| ResolvedDeployment {
+            deployment_id: d.id.clone(),
+            provider_name: provider.name.clone(),
+            resource_group: d.resource_group.clone(),
+        }));
+}
+
+/// Deployment ids and model version for logging
+fn describe(deployments: &[&RunningDeployment]) -> String {
+    let ids: Vec<&str> = deployments.iter().map(|d| d.id.as_str()).collect();
+    let version = deployments
+        .first()
+        .and_then(|d| d.model_version.as_deref())
+        .unwrap_or("unknown");
+    format!("deployment_ids: {}, version: {}", ids.join(", "), version)
+}
+
+/// Check if a glob pattern matches a string. `*` matches any run of
+/// characters, anywhere in the pattern.
+/// Returns the specificity (number of literal characters) if matches, None otherwise.
+/// Higher specificity = more specific match.
+pub fn glob_matches(pattern: &str, input: &str) -> Option<usize> {
+    let literal_len = pattern.len() - pattern.matches('*').count();
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let rest: Vec<&str> = parts.collect();
+
+    // No wildcard: exact match only
+    let Some((last, middle)) = rest.split_last() else {
+        return (pattern == input).then_some(literal_len);
+    };
+
+    let mut remaining = input.strip_prefix(first)?;
+    for part in middle {
+        let start = remaining.find(part)?;
+        remaining = &remaining[start + part.len()..];
+    }
+    remaining.ends_with(last).then_some(literal_len)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -363,12 +1089,75 @@ mod tests {
         assert_eq!(general.unwrap(), 7);
     }
 
+    fn running(
+        id: &str,
+        version: &str,
+        configuration_name: Option<&str>,
+        started_at: &str,
+    ) -> RunningDeployment {
+        RunningDeployment {
+            id: id.to_string(),
+            resource_group: "default".to_string(),
+            model_version: Some(version.to_string()),
+            configuration_name: configuration_name.map(str::to_string),
+            started_at: started_at.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_select_deployments() {
+        let aicore_deployments: HashMap<String, Vec<RunningDeployment>> = HashMap::from([
+            (
+                "gpt-4o".to_string(),
+                vec![
+                    running("d-old", "2024-08-06", Some("gpt-4o-pinned"), "2024-09-01"),
+                    running("d-latest-1", "latest", None, "2025-03-01"),
+                    running("d-old-2", "2024-08-06", None, "2025-04-01"),
+                    running("d-latest-2", "latest", None, "2025-01-01"),
+                ],
+            ),
+            (
+                "gpt-4.1".to_string(),
+                vec![running("d-41", "2025-04-14", None, "2025-05-01")],
+            ),
+        ]);
+        let models: Vec<Model> = serde_yaml::from_str(
+            r#"
+- name: gpt-4o
+- name: gpt-4o:latest
+  aicore_model_name: gpt-4o
+  aicore_model_version: latest
+- name: gpt-4o-pinned
+  aicore_model_name: gpt-4o
+  configuration_name: gpt-4o-pinned
+- name: gpt-4o-eu
+  aicore_model_name: gpt-4o
+  deployment_ids: {eu10: d-41}
+"#,
+        )
+        .unwrap();
+        let selected = |model: &Model, provider: &str| -> Vec<String> {
+            select_deployments(model, provider, &aicore_deployments)
+                .into_iter()
+                .map(|d| d.id.clone())
+                .collect()
+        };
+
+        // The version started most recently wins, with all its deployments
+        assert_eq!(selected(&models[0], "eu10"), ["d-old", "d-old-2"]);
+        assert_eq!(selected(&models[1], "eu10"), ["d-latest-1", "d-latest-2"]);
+        assert_eq!(selected(&models[2], "eu10"), ["d-old"]);
+        // A pinned deployment id applies to its provider only
+        assert_eq!(selected(&models[3], "eu10"), ["d-41"]);
+        assert_eq!(selected(&models[3], "us10"), ["d-old", "d-old-2"]);
+    }
+
     fn create_test_registry(models: Vec<Model>) -> ModelRegistry {
         ModelRegistry::new(
             models,
             FallbackModels::default(),
             vec![],
-            TokenManager::new(vec!["test".to_string()]),
+            TokenManager::new(),
             600,
         )
     }
@@ -378,6 +1167,11 @@ mod tests {
         let models = vec![Model {
             name: "claude-sonnet-4-5".to_string(),
             aicore_model_name: None,
+            aicore_model_version: None,
+            configuration_name: None,
+            deployment_ids: Default::default(),
+            timeouts: Default::default(),
+            request: Default::default(),
             aliases: vec!["claude-4-sonnet".to_string()],
         }];
         let registry = create_test_registry(models);
@@ -392,6 +1186,11 @@ mod tests {
         let models = vec![Model {
             name: "claude-sonnet-4-5".to_string(),
             aicore_model_name: None,
+            aicore_model_version: None,
+            configuration_name: None,
+            deployment_ids: Default::default(),
+            timeouts: Default::default(),
+            request: Default::default(),
             aliases: vec!["claude-sonnet-4-5-*".to_string()],
         }];
         let registry = create_test_registry(models);
@@ -407,11 +1206,21 @@ mod tests {
             Model {
                 name: "claude-general".to_string(),
                 aicore_model_name: None,
+                aicore_model_version: None,
+                configuration_name: None,
+                deployment_ids: Default::default(),
+                timeouts: Default::default(),
+                request: Default::default(),
                 aliases: vec!["claude-*".to_string()],
             },
             Model {
                 name: "claude-sonnet-4-5".to_string(),
                 aicore_model_name: None,
+                aicore_model_version: None,
+                configuration_name: None,
+                deployment_ids: Default::default(),
+                timeouts: Default::default(),
+                request: Default::default(),
                 aliases: vec!["claude-sonnet-4-5-*".to_string()],
             },
         ];
@@ -433,6 +1242,11 @@ mod tests {
         let models = vec![Model {
             name: "claude-sonnet-4-5".to_string(),
             aicore_model_name: None,
+            aicore_model_version: None,
+            configuration_name: None,
+            deployment_ids: Default::default(),
+            timeouts: Default::default(),
+            request: Default::default(),
             aliases: vec!["claude-sonnet-4-5-*".to_string()],
         }];
         let registry = create_test_registry(models);
@@ -446,6 +1260,11 @@ mod tests {
         let models = vec![Model {
             name: "claude-sonnet-4-5".to_string(),
             aicore_model_name: None,
+            aicore_model_version: None,
+            configuration_name: None,
+            deployment_ids: Default::default(),
+            timeouts: Default::default(),
+            request: Default::default(),
             aliases: vec![
                 "claude-sonnet-4-5-*".to_string(),
                 "claude-4-sonnet".to_string(),
@@ -472,4 +1291,429 @@ mod tests {
             Some(&"claude-sonnet-4-5".to_string())
         );
     }
+
+    #[test]
+    fn test_glob_matches_middle_wildcard() {
+        assert_eq!(glob_matches("claude-*-4-5", "claude-sonnet-4-5"), Some(11));
+        assert_eq!(glob_matches("claude-*-4-5", "claude-sonnet-4-5-x"), None);
+        assert_eq!(glob_matches("gpt-*-mini-*", "gpt-4o-mini-2024"), Some(10));
+        assert_eq!(glob_matches("a*a", "a"), None);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_model() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+providers: []
+api_keys: []
+models:
+  - name: claude-sonnet-4-5
+    aliases: ['(?i)^sonnet[ -]?4\.5$']
+  - name: claude-opus-4-5
+  - name: gpt-4o
+fallback_models:
+  openai: gpt-4o
+alias_rewrites:
+  - pattern: '^claude-(\w+)-4-5-\d{8}$'
+    target: claude-$1-4-5
+"#,
+        )
+        .unwrap();
+        let registry = ModelRegistry::from_config(&config, TokenManager::new());
+
+        let resolved = async |name: &str| registry.resolve_model(name).await;
+        assert_eq!(resolved("gpt-4o").await.resolved_by, ResolvedBy::Name);
+
+        let alias = resolved("Sonnet 4.5").await;
+        assert_eq!(alias.model, "claude-sonnet-4-5");
+        assert!(matches!(alias.resolved_by, ResolvedBy::Alias { .. }));
+
+        let rewrite = resolved("claude-opus-4-5-20251101").await;
+        assert_eq!(rewrite.model, "claude-opus-4-5");
+        assert_eq!(
+            rewrite.resolved_by,
+            ResolvedBy::Alias {
+                rule: r"rewrite '^claude-(\w+)-4-5-\d{8}$' to 'claude-$1-4-5'".to_string()
+            }
+        );
+
+        let fallback = resolved("gpt-3.5-turbo").await;
+        assert_eq!(fallback.model, "gpt-4o");
+        assert!(matches!(fallback.resolved_by, ResolvedBy::Fallback { .. }));
+
+        assert_eq!(
+            resolved("mistral-large").await.resolved_by,
+            ResolvedBy::Unchanged
+        );
+    }
+
+    #[tokio::test]
+    async fn test_discovered_models() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+providers: []
+api_keys: []
+models:
+  - name: claude-sonnet-4-5
+    aicore_model_name: anthropic--claude-4.5-sonnet
+    aliases: ["claude-*"]
+  - name: gpt-4o
+    aicore_model_name: gpt-4o-custom
+discovery:
+  enabled: true
+  strip_vendor_prefix: true
+  exclude: ["*embedding*"]
+"#,
+        )
+        .unwrap();
+        let registry = ModelRegistry::from_config(&config, TokenManager::new());
+        let provider: Provider = serde_yaml::from_str(
+            "name: eu10\nuaa_token_url: u\nuaa_client_id: c\nuaa_client_secret: s\ngenai_api_url: g\nresource_group: default\n",
+        )
+        .unwrap();
+        let mut aicore_deployments: HashMap<String, Vec<RunningDeployment>> = [
+            ("anthropic--claude-4.5-sonnet", "d-sonnet"),
+            ("anthropic--claude-4-opus", "d-opus"),
+            ("gpt-4o", "d-gpt-4o"),
+            ("text-embedding-3-large", "d-embedding"),
+        ]
+        .into_iter()
+        .map(|(name, id)| (name.to_string(), vec![running(id, "1", None, "2025-01-01")]))
+        .collect();
+        aicore_deployments
+            .get_mut("anthropic--claude-4-opus")
+            .unwrap()
+            .extend([
+                RunningDeployment {
+                    resource_group: "team-b".to_string(),
+                    ..running("d-opus-2", "1", None, "2025-02-01")
+                },
+                running("d-opus-old", "0", None, "2024-01-01"),
+            ]);
+
+        let mut resolved = HashMap::new();
+        registry.add_discovered(&provider, &aicore_deployments, &mut resolved);
+        let mut names: Vec<&String> = resolved.keys().collect();
+        names.sort();
+        // Configured models claim their AI Core model and name; embeddings are excluded
+        assert_eq!(names, ["claude-4-opus"]);
+        assert_eq!(resolved["claude-4-opus"][0].deployment_id, "d-opus");
+
+        *registry.resolved_models.write().await = resolved;
+        // Every running deployment of the newest version is kept, in listing
+        // order, with the resource group it runs in
+        let deployments: Vec<(String, String)> = registry
+            .get_deployments_for_provider("claude-4-opus", "eu10")
+            .await
+            .into_iter()
+            .map(|d| (d.deployment_id, d.resource_group))
+            .collect();
+        assert_eq!(
+            deployments,
+            [
+                ("d-opus".to_string(), "default".to_string()),
+                ("d-opus-2".to_string(), "team-b".to_string())
+            ]
+        );
+        assert!(
+            registry
+                .get_deployments_for_provider("claude-4-opus", "us10")
+                .await
+                .is_empty()
+        );
+        let resolution = registry.resolve_model("claude-4-opus").await;
+        assert_eq!(resolution.model, "claude-4-opus");
+        assert_eq!(resolution.resolved_by, ResolvedBy::Discovered);
+        // Without a deployment the alias still applies
+        let resolution = registry.resolve_model("claude-4-haiku").await;
+        assert_eq!(resolution.model, "claude-sonnet-4-5");
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_survives_failed_refresh() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("registry.json");
+        // Nothing listens on port 1, so every refresh fails
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+providers:
+  - name: eu10
+    uaa_token_url: http://127.0.0.1:1/oauth/token
+    uaa_client_id: c
+    uaa_client_secret: s
+    genai_api_url: http://127.0.0.1:1
+api_keys: []
+models:
+  - name: gpt-4o
+registry_snapshot:
+  path: {}
+"#,
+            path.display()
+        ))
+        .unwrap();
+        let deployment = ResolvedDeployment {
+            deployment_id: "d-gpt-4o".to_string(),
+            provider_name: "eu10".to_string(),
+            resource_group: "default".to_string(),
+        };
+        let models = HashMap::from([
+            ("gpt-4o".to_string(), vec![deployment.clone()]),
+            ("gpt-3.5".to_string(), vec![deployment]),
+        ]);
+
+        let previous = ModelRegistry::from_config(&config, TokenManager::new());
+        let refreshed_at = |time| HashMap::from([("eu10".to_string(), time)]);
+        previous.save_snapshot(&models, &refreshed_at(SystemTime::now()));
+
+        // Loaded at startup and kept when the refresh fails; models that are
+        // no longer configured are dropped
+        let registry = ModelRegistry::from_config(&config, TokenManager::new());
+        registry.start().await.unwrap();
+        registry.stop();
+        assert_eq!(registry.get_available_models().await, ["gpt-4o"]);
+        registry.refresh_deployments().await.unwrap();
+        assert_eq!(registry.get_available_models().await, ["gpt-4o"]);
+        assert!(!registry.has_refreshed());
+        let status = &registry.provider_status().await["eu10"];
+        assert!(status.stale);
+        assert!(status.last_error.is_some());
+
+        // Too old to be used
+        let stale = SystemTime::now() - Duration::from_secs(2 * 86400);
+        previous.save_snapshot(&models, &refreshed_at(stale));
+        let registry = ModelRegistry::from_config(&config, TokenManager::new());
+        assert!(registry.load_snapshot().unwrap().models.is_empty());
+        registry.start().await.unwrap();
+        registry.stop();
+        assert!(registry.get_available_models().await.is_empty());
+    }
+
+    #[derive(Clone, Default)]
+    struct FakeAiCore {
+        hanging: Arc<AtomicBool>,
+        listed: Arc<std::sync::atomic::AtomicUsize>,
+        /// Resource groups whose deployments fail to list
+        failing: Arc<Mutex<Vec<String>>>,
+    }
+
+    async fn list_deployments(
+        axum::extract::State(aicore): axum::extract::State<FakeAiCore>,
+        headers: axum::http::HeaderMap,
+    ) -> Result<axum::Json<serde_json::Value>, axum::http::StatusCode> {
+        aicore.listed.fetch_add(1, Ordering::SeqCst);
+        if aicore.hanging.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+        }
+        let group = headers
+            .get("AI-Resource-Group")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if aicore.failing.lock().unwrap().contains(&group) {
+            return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        let (id, model) = match group.as_str() {
+            "default" => ("d-gpt-4o".to_string(), "gpt-4o"),
+            _ => (format!("d-{group}"), "gpt-4o-mini"),
+        };
+        Ok(axum::Json(serde_json::json!({
+            "count": 1,
+            "resources": [{
+                "id": id,
+                "createdAt": "2025-01-01T00:00:00Z",
+                "modifiedAt": "2025-01-01T00:00:00Z",
+                "status": "RUNNING",
+                "scenarioId": "foundation-models",
+                "configurationId": "c",
+                "details": {"resources": {"backendDetails": {"model": {"name": model, "version": "1"}}}}
+            }]
+        })))
+    }
+
+    /// Serve UAA tokens and a single deployment per resource group: gpt-4o in
+    /// `default`, gpt-4o-mini in any other; returns the provider config entry
+    /// pointing to it
+    async fn start_fake_aicore(name: &str) -> (FakeAiCore, String) {
+        use axum::routing::{get, post};
+
+        let aicore = FakeAiCore::default();
+        let app = axum::Router::new()
+            .route(
+                "/oauth/token",
+                post(|| async {
+                    axum::Json(serde_json::json!({"access_token": "token", "expires_in": 3600}))
+                }),
+            )
+            .route("/v2/lm/deployments", get(list_deployments))
+            .with_state(aicore.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let provider = format!(
+            r#"
+  - name: {name}
+    uaa_token_url: http://{addr}/oauth/token
+    uaa_client_id: c
+    uaa_client_secret: s
+    genai_api_url: http://{addr}"#
+        );
+        (aicore, provider)
+    }
+
+    #[tokio::test]
+    async fn test_hanging_provider_keeps_its_deployments() {
+        let (_, eu10) = start_fake_aicore("eu10").await;
+        let (us10_aicore, us10) = start_fake_aicore("us10").await;
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+providers:{eu10}{us10}
+api_keys: []
+models:
+  - name: gpt-4o
+refresh_timeout_secs: 1
+"#
+        ))
+        .unwrap();
+        let registry = ModelRegistry::from_config(&config, TokenManager::new());
+        let providers = |deployments: Vec<ResolvedDeployment>| {
+            deployments
+                .into_iter()
+                .map(|d| d.provider_name)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(!registry.has_refreshed());
+        registry.refresh_deployments().await.unwrap();
+        assert!(registry.has_refreshed());
+        assert_eq!(
+            providers(registry.get_providers_for_model("gpt-4o").await),
+            ["eu10", "us10"]
+        );
+
+        // us10 times out without holding up eu10 and keeps its entries
+        us10_aicore.hanging.store(true, Ordering::SeqCst);
+        let started = std::time::Instant::now();
+        registry.refresh_deployments().await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(
+            providers(registry.get_providers_for_model("gpt-4o").await),
+            ["eu10", "us10"]
+        );
+
+        let status = registry.provider_status().await;
+        assert!(!status["eu10"].stale);
+        assert!(status["eu10"].last_error.is_none());
+        assert!(status["us10"].stale);
+        assert_eq!(
+            status["us10"].last_error.as_deref(),
+            Some("timed out after 1s")
+        );
+        assert!(status["us10"].last_success < status["eu10"].last_success);
+    }
+
+    #[tokio::test]
+    async fn test_failed_resource_group_keeps_its_deployments() {
+        let (aicore, eu10) = start_fake_aicore("eu10").await;
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+providers:{eu10}
+    resource_groups: [default, team-b]
+api_keys: []
+models:
+  - name: gpt-4o
+  - name: gpt-4o-mini
+"#
+        ))
+        .unwrap();
+        let registry = ModelRegistry::from_config(&config, TokenManager::new());
+        let deployments = async |model: &str| {
+            registry
+                .get_providers_for_model(model)
+                .await
+                .into_iter()
+                .map(|d| (d.deployment_id, d.resource_group))
+                .collect::<Vec<_>>()
+        };
+
+        registry.refresh_deployments().await.unwrap();
+        let mini = vec![("d-team-b".to_string(), "team-b".to_string())];
+        assert_eq!(deployments("gpt-4o-mini").await, mini);
+
+        // team-b fails while default still lists
+        aicore.failing.lock().unwrap().push("team-b".to_string());
+        registry.refresh_deployments().await.unwrap();
+        assert_eq!(deployments("gpt-4o-mini").await, mini);
+        assert_eq!(deployments("gpt-4o").await.len(), 1);
+        let status = &registry.provider_status().await["eu10"];
+        assert!(status.stale);
+        assert!(
+            status
+                .last_error
+                .as_deref()
+                .is_some_and(|e| e.contains("team-b"))
+        );
+        assert!(status.group_success["team-b"] < status.group_success["default"]);
+
+        aicore.failing.lock().unwrap().clear();
+        registry.refresh_deployments().await.unwrap();
+        let status = &registry.provider_status().await["eu10"];
+        assert!(!status.stale);
+        assert!(status.last_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_evicted_deployment_is_restored_by_targeted_refresh() {
+        let (eu10_aicore, eu10) = start_fake_aicore("eu10").await;
+        let (us10_aicore, us10) = start_fake_aicore("us10").await;
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+providers:{eu10}{us10}
+api_keys: []
+models:
+  - name: gpt-4o
+"#
+        ))
+        .unwrap();
+        let registry = ModelRegistry::from_config(&config, TokenManager::new());
+        registry.refresh_deployments().await.unwrap();
+
+        registry.evict_deployment("us10", "d-gpt-4o").await;
+        assert!(
+            registry
+                .get_deployments_for_provider("gpt-4o", "us10")
+                .await
+                .is_empty()
+        );
+        assert_eq!(
+            registry
+                .get_deployments_for_provider("gpt-4o", "eu10")
+                .await[0]
+                .deployment_id,
+            "d-gpt-4o"
+        );
+
+        // A burst of errors refreshes the provider once, and only that provider
+        registry.request_provider_refresh("us10");
+        registry.request_provider_refresh("us10");
+        for _ in 0..50 {
+            if !registry
+                .get_deployments_for_provider("gpt-4o", "us10")
+                .await
+                .is_empty()
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(
+            registry
+                .get_deployments_for_provider("gpt-4o", "us10")
+                .await[0]
+                .deployment_id,
+            "d-gpt-4o"
+        );
+        assert_eq!(us10_aicore.listed.load(Ordering::SeqCst), 2);
+        assert_eq!(eu10_aicore.listed.load(Ordering::SeqCst), 1);
+    }
 }
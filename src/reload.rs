@@ -0,0 +1,442 @@
+//! Hot reload of the configuration file, triggered by file changes or SIGHUP.
+//!
+//! A reload loads and validates the new config, then swaps a new [`Snapshot`]
+//! into [`AppState`]. Requests already in flight keep the snapshot they started
+//! with; cached OAuth tokens survive the reload.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, mpsc};
+use tracing::{error, info, warn};
+
+use crate::{
+    auth::Authenticator,
+    config::Config,
+    constants::reload::DEBOUNCE_MS,
+    http,
+    registry::ModelRegistry,
+    routes::{AppState, Snapshot},
+};
+
+/// Kubernetes ConfigMap volumes update files by swapping this symlinked directory
+const CONFIGMAP_DATA_DIR: &str = "..data";
+
+pub struct ConfigReloader {
+    config_path: Option<String>,
+    /// Port given on the command line, which takes precedence over the file
+    port_override: Option<u16>,
+    state: AppState,
+    /// Serializes reloads triggered by the watcher and by signals
+    reloading: Mutex<()>,
+}
+
+impl ConfigReloader {
+    pub fn new(config_path: Option<String>, port_override: Option<u16>, state: AppState) -> Self {
+        Self {
+            config_path,
+            port_override,
+            state,
+            reloading: Mutex::new(()),
+        }
+    }
+
+    /// Load the config file again and apply it if it is valid.
+    /// On error the running configuration is left untouched.
+    pub async fn reload(&self) -> Result<()> {
+        let _guard = self.reloading.lock().await;
+
+        let mut config = Config::load(self.config_path.as_deref())?;
+        if let Some(port) = self.port_override {
+            config.port = port;
+        }
+        // Pick up rotated CA and client certificates, which `load` has just checked
+        http::reset();
+
+        let current = self.state.snapshot();
+        let load_balancer = current
+            .load_balancer
+            .reconfigured(config.providers.clone(), config.load_balancing.clone());
+        if load_balancer.is_empty() {
+            return Err(anyhow::anyhow!("No enabled providers configured"));
+        }
+
+        let changes = diff_configs(&current.config, &config);
+        if changes.is_empty() {
+            info!("Configuration reloaded: no changes");
+            return Ok(());
+        }
+        for change in &changes {
+            info!("Config change: {}", change);
+        }
+
+        let model_registry = ModelRegistry::from_config(&config, self.state.token_manager.clone());
+        model_registry.seed_from(&current.model_registry).await;
+
+        self.state.snapshot.store(Arc::new(Snapshot {
+            authenticator: Authenticator::from_config(&config),
+            model_registry: model_registry.clone(),
+            load_balancer,
+            config,
+        }));
+        current.model_registry.stop();
+
+        info!("Configuration reloaded ({} changes)", changes.len());
+
+        model_registry
+            .start()
+            .await
+            .context("Failed to refresh model registry after reload")
+    }
+
+    /// Reload on changes to the config file and on SIGHUP.
+    pub fn spawn(self: Arc<Self>) -> Result<()> {
+        let (tx, mut rx) = mpsc::channel::<()>(1);
+
+        let path = PathBuf::from(Config::file_path(self.config_path.as_deref())?);
+        let watcher = watch_file(&path, tx.clone())?;
+        info!("Watching {} for configuration changes", path.display());
+
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+
+            let mut hangup = signal(SignalKind::hangup()).context("Failed to listen for SIGHUP")?;
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                while hangup.recv().await.is_some() {
+                    info!("Received SIGHUP, reloading configuration");
+                    let _ = tx.try_send(());
+                }
+            });
+        }
+
+        tokio::spawn(async move {
+            // The watcher stops when dropped
+            let _watcher = watcher;
+            while rx.recv().await.is_some() {
+                // Editors and ConfigMap updates produce bursts of events
+                tokio::time::sleep(Duration::from_millis(DEBOUNCE_MS)).await;
+                while rx.try_recv().is_ok() {}
+
+                if let Err(e) = self.reload().await {
+                    error!(
+                        "Configuration reload failed, keeping current configuration: {:#}",
+                        e
+                    );
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// Watch the directory containing a file, so that files replaced by rename (as
+/// most editors and Kubernetes volume updates do) are picked up as well.
+pub fn watch_file(path: &Path, tx: mpsc::Sender<()>) -> Result<notify::RecommendedWatcher> {
+    use notify::{EventKind, RecursiveMode, Watcher};
+
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .with_context(|| format!("Invalid file path: {}", path.display()))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("File watch error: {}", e);
+                return;
+            }
+        };
+        if !matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) {
+            return;
+        }
+        let relevant = event.paths.iter().any(|p| {
+            p.file_name()
+                .is_some_and(|name| name == file_name || name == CONFIGMAP_DATA_DIR)
+        });
+        if relevant {
+            let _ = tx.try_send(());
+        }
+    })
+    .context("Failed to create file watcher")?;
+
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {}", dir.display()))?;
+
+    Ok(watcher)
+}
+
+/// Describe what changed between two configurations, one line per change.
+/// Secrets are never included.
+pub fn diff_configs(old: &Config, new: &Config) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    for provider in &new.providers {
+        match old.providers.iter().find(|p| p.name == provider.name) {
+            None => changes.push(format!("provider '{}' added", provider.name)),
+            Some(previous) if previous != provider => {
+                changes.push(format!("provider '{}' changed", provider.name))
+            }
+            Some(_) => {}
+        }
+    }
+    for provider in &old.providers {
+        if !new.providers.iter().any(|p| p.name == provider.name) {
+            changes.push(format!("provider '{}' removed", provider.name));
+        }
+    }
+
+    for model in &new.models {
+        match old.models.iter().find(|m| m.name == model.name) {
+            None => changes.push(format!("model '{}' added", model.name)),
+            Some(previous) if previous != model => {
+                changes.push(format!("model '{}' changed", model.name))
+            }
+            Some(_) => {}
+        }
+    }
+    for model in &old.models {
+        if !new.models.iter().any(|m| m.name == model.name) {
+            changes.push(format!("model '{}' removed", model.name));
+        }
+    }
+
+    let added_keys = new
+        .api_keys
+        .iter()
+        .filter(|k| !old.api_keys.contains(k))
+        .count();
+    let removed_keys = old
+        .api_keys
+        .iter()
+        .filter(|k| !new.api_keys.contains(k))
+        .count();
+    if added_keys > 0 || removed_keys > 0 {
+        changes.push(format!(
+            "api keys: {added_keys} added, {removed_keys} removed"
+        ));
+    }
+
+    let added_keys = new
+        .admin_api_keys
+        .iter()
+        .filter(|k| !old.admin_api_keys.contains(k))
+        .count();
+    let removed_keys = old
+        .admin_api_keys
+        .iter()
+        .filter(|k| !new.admin_api_keys.contains(k))
+        .count();
+    if added_keys > 0 || removed_keys > 0 {
+        changes.push(format!(
+            "admin api keys: {added_keys} added, {removed_keys} removed"
+        ));
+    }
+
+    match (&old.jwt_auth, &new.jwt_auth) {
+        (None, Some(_)) => changes.push("jwt_auth enabled".to_string()),
+        (Some(_), None) => changes.push("jwt_auth disabled".to_string()),
+        (Some(previous), Some(jwt)) if previous != jwt => {
+            changes.push("jwt_auth changed".to_string())
+        }
+        _ => {}
+    }
+
+    if old.http != new.http {
+        changes.push("http settings changed".to_string());
+    }
+
+    if old.timeouts != new.timeouts {
+        changes.push("timeouts changed".to_string());
+    }
+
+    if old.body_rules != new.body_rules {
+        changes.push("body_rules changed".to_string());
+    }
+
+    if old.alias_rewrites != new.alias_rewrites {
+        changes.push("alias_rewrites changed".to_string());
+    }
+
+    if old.discovery != new.discovery {
+        changes.push("discovery changed".to_string());
+    }
+
+    if old.registry_snapshot != new.registry_snapshot {
+        changes.push("registry_snapshot changed".to_string());
+    }
+
+    if old.fallback_models != new.fallback_models {
+        changes.push("fallback_models changed".to_string());
+    }
+    if old.load_balancing != new.load_balancing {
+        changes.push(format!(
+            "load_balancing: {:?} -> {:?}",
+            old.load_balancing, new.load_balancing
+        ));
+    }
+    if old.refresh_interval_secs != new.refresh_interval_secs {
+        changes.push(format!(
+            "refresh_interval_secs: {} -> {}",
+            old.refresh_interval_secs, new.refresh_interval_secs
+        ));
+    }
+    if old.refresh_timeout_secs != new.refresh_timeout_secs {
+        changes.push(format!(
+            "refresh_timeout_secs: {} -> {}",
+            old.refresh_timeout_secs, new.refresh_timeout_secs
+        ));
+    }
+
+    // Applied at startup only
+    if old.port != new.port {
+        changes.push(format!(
+            "port: {} -> {} (requires restart)",
+            old.port, new.port
+        ));
+    }
+    if old.log_level != new.log_level {
+        changes.push(format!(
+            "log_level: {} -> {} (requires restart)",
+            old.log_level, new.log_level
+        ));
+    }
+    if old.listen != new.listen {
+        changes.push("listen changed (requires restart)".to_string());
+    }
+    if old.request_body_limit != new.request_body_limit {
+        changes.push("request_body_limit changed (requires restart)".to_string());
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{balancer::LoadBalancer, token::TokenManager};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn config_yaml(api_key: &str, providers: &[&str]) -> String {
+        let mut yaml = format!("api_keys:\n  - {api_key}\nmodels:\n  - name: gpt-4o\nproviders:\n");
+        for name in providers {
+            // Unroutable endpoints: registry refreshes fail fast and are skipped
+            yaml.push_str(&format!(
+                "  - name: {name}\n    uaa_token_url: http://127.0.0.1:1/oauth/token\n    uaa_client_id: {name}-client\n    uaa_client_secret: {name}-secret\n    genai_api_url: http://127.0.0.1:1\n"
+            ));
+        }
+        yaml
+    }
+
+    fn app_state(config: Config) -> AppState {
+        let token_manager = TokenManager::new();
+        let model_registry = ModelRegistry::from_config(&config, token_manager.clone());
+        let snapshot = Snapshot {
+            authenticator: Authenticator::from_config(&config),
+            load_balancer: LoadBalancer::new(
+                config.providers.clone(),
+                config.load_balancing.clone(),
+            ),
+            model_registry,
+            config,
+        };
+        AppState::new(snapshot, token_manager)
+    }
+
+    #[tokio::test]
+    async fn test_reload_swaps_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        fs::write(&path, config_yaml("old-key", &["eu10"])).unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let state = app_state(Config::load(Some(&path)).unwrap());
+        let reloader = ConfigReloader::new(Some(path.clone()), Some(9999), state.clone());
+        let in_flight = state.snapshot();
+
+        fs::write(&path, config_yaml("new-key", &["eu10", "us10"])).unwrap();
+        reloader.reload().await.unwrap();
+
+        let current = state.snapshot();
+        assert_eq!(current.load_balancer.len(), 2);
+        assert_eq!(current.config.port, 9999);
+        assert!(current.authenticator.authenticate("new-key").await.is_ok());
+        assert!(current.authenticator.authenticate("old-key").await.is_err());
+
+        // A request that started before the reload keeps its snapshot
+        assert_eq!(in_flight.load_balancer.len(), 1);
+        assert_eq!(in_flight.config.api_keys, vec!["old-key".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_reload_keeps_current_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        fs::write(&path, config_yaml("key", &["eu10"])).unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let state = app_state(Config::load(Some(&path)).unwrap());
+        let reloader = ConfigReloader::new(Some(path.clone()), None, state.clone());
+        let before = state.snapshot();
+
+        fs::write(&path, "api_keys: [key]\nproviders: [{name: broken}]\n").unwrap();
+        assert!(reloader.reload().await.is_err());
+
+        let yaml =
+            config_yaml("key", &["eu10"]).replace("eu10-secret", "eu10-secret\n    enabled: false");
+        fs::write(&path, yaml).unwrap();
+        let error = reloader.reload().await.unwrap_err();
+        assert!(error.to_string().contains("No enabled providers"));
+
+        assert!(Arc::ptr_eq(&before, &state.snapshot()));
+    }
+
+    #[test]
+    fn test_diff_configs() {
+        let temp_dir = TempDir::new().unwrap();
+        let old_path = temp_dir.path().join("old.yaml");
+        let new_path = temp_dir.path().join("new.yaml");
+        fs::write(&old_path, config_yaml("key-a", &["eu10", "us10"])).unwrap();
+        fs::write(
+            &new_path,
+            config_yaml("key-b", &["eu10", "jp10"]).replace("eu10-client", "eu10-rotated")
+                + "port: 9000\n",
+        )
+        .unwrap();
+
+        let old = Config::load(Some(old_path.to_str().unwrap())).unwrap();
+        let new = Config::load(Some(new_path.to_str().unwrap())).unwrap();
+
+        assert!(diff_configs(&old, &old).is_empty());
+
+        let changes = diff_configs(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                "provider 'eu10' changed",
+                "provider 'jp10' added",
+                "provider 'us10' removed",
+                "api keys: 1 added, 1 removed",
+                "port: 8900 -> 9000 (requires restart)",
+            ]
+        );
+        assert!(
+            !changes
+                .iter()
+                .any(|c| c.contains("key-a") || c.contains("key-b"))
+        );
+    }
+}
@@ -1,63 +1,232 @@
+use arc_swap::ArcSwap;
 use axum::{
-    Router,
-    extract::{Path, State},
+    Extension, Router,
+    body::Body,
+    extract::{ConnectInfo, Path, State},
     http::{HeaderMap, Method, StatusCode},
     response::{IntoResponse, Json, Response},
     routing::{get, post},
 };
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
 use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::SystemTime;
 use thiserror::Error;
 
 use crate::{
-    balancer::LoadBalancer,
-    config::Config,
+    admin,
+    auth::Authenticator,
+    balancer::{InFlightGuard, LoadBalancer, ProviderMode},
+    config::{Config, RouteGroup},
+    errors::AuthError,
+    http,
+    listener::PeerInfo,
     proxy::{ProxyExecuteResult, ProxyRequestBuilder, ProxyRequestParams},
     registry::ModelRegistry,
     token::TokenManager,
 };
 
-#[derive(Clone)]
-pub struct AppState {
+/// Everything derived from the config file, replaced as a whole on reload.
+pub struct Snapshot {
     pub config: Config,
+    pub authenticator: Authenticator,
     pub model_registry: ModelRegistry,
-    pub token_manager: TokenManager,
     pub load_balancer: LoadBalancer,
-    pub client: reqwest::Client,
 }
 
-pub fn create_router(state: AppState) -> Router {
-    Router::new()
+#[derive(Clone)]
+pub struct AppState {
+    pub snapshot: Arc<ArcSwap<Snapshot>>,
+    pub token_manager: TokenManager,
+}
+
+impl AppState {
+    pub fn new(snapshot: Snapshot, token_manager: TokenManager) -> Self {
+        Self {
+            snapshot: Arc::new(ArcSwap::from_pointee(snapshot)),
+            token_manager,
+        }
+    }
+
+    /// Current snapshot; a request holds on to it until it completes.
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.snapshot.load_full()
+    }
+}
+
+/// Build the router for a listener serving the given endpoint groups.
+/// `/health` and `/ready` are served on every listener.
+pub fn create_router(state: AppState, groups: &[RouteGroup]) -> Router {
+    let mut router = Router::new()
         .route("/health", get(health_check))
-        .route("/v1/models", get(get_models))
-        .route("/v1/chat/completions", post(handle_openai_chat))
-        .route(
-            "/openai/deployments/{model}/chat/completions",
-            post(handle_azure_openai),
-        )
-        .route(
-            "/openai/deployments/{model}/embedding",
-            post(handle_azure_openai),
-        )
-        .route("/v1/messages", post(handle_claude_messages))
-        .route(
-            "/gemini/models/{model_operation}",
-            post(handle_gemini_models),
-        )
-        .route(
-            "/gemini/v1beta/models/{model_operation}",
-            post(handle_gemini_models),
-        )
-        .route(
-            "/v1beta/models/{model_operation}",
-            post(handle_gemini_models),
-        )
-        .with_state(state)
+        .route("/ready", get(readiness_check));
+
+    if groups.contains(&RouteGroup::Api) {
+        router = router
+            .route("/v1/models", get(get_models))
+            .route("/v1/chat/completions", post(handle_openai_chat))
+            .route(
+                "/openai/deployments/{model}/chat/completions",
+                post(handle_azure_openai),
+            )
+            .route(
+                "/openai/deployments/{model}/embedding",
+                post(handle_azure_openai),
+            )
+            .route("/v1/messages", post(handle_claude_messages))
+            .route(
+                "/gemini/models/{model_operation}",
+                post(handle_gemini_models),
+            )
+            .route(
+                "/gemini/v1beta/models/{model_operation}",
+                post(handle_gemini_models),
+            )
+            .route(
+                "/v1beta/models/{model_operation}",
+                post(handle_gemini_models),
+            );
+    }
+    if groups.contains(&RouteGroup::Admin) {
+        router = router.merge(admin::routes());
+    }
+
+    router.with_state(state)
 }
 
 pub async fn health_check() -> impl IntoResponse {
     "OK"
 }
 
+/// Readiness of one provider
+pub struct ProviderReadiness {
+    pub name: String,
+    /// Enabled in the config and by the admin API
+    pub routable: bool,
+    pub token_valid: bool,
+    pub models: usize,
+    /// Deployments were listed by the latest refresh
+    pub refreshed: bool,
+    /// Some deployments are kept from an earlier refresh or the snapshot
+    pub stale: bool,
+    pub last_success: Option<SystemTime>,
+    pub last_error: Option<String>,
+}
+
+/// Whether the router can serve requests, with a breakdown per provider
+pub struct Readiness {
+    pub ready: bool,
+    pub refreshed: bool,
+    pub valid_token: bool,
+    /// Models with a deployment on a routable provider that has a valid token
+    pub models: usize,
+    pub providers: Vec<ProviderReadiness>,
+}
+
+impl Readiness {
+    /// Ready once deployments have been refreshed, while at least one
+    /// routable provider has both a valid token and resolved models.
+    pub async fn check(state: &AppState, snapshot: &Snapshot) -> Self {
+        let registry = &snapshot.model_registry;
+        let refresh_status = registry.provider_status().await;
+        let resolved = registry.resolved_models().await;
+
+        let mut valid_token = false;
+        let mut serving = Vec::new();
+        let mut providers = Vec::new();
+        for provider in &snapshot.config.providers {
+            let routable = provider.enabled
+                && snapshot.load_balancer.provider_mode(&provider.name) == ProviderMode::Enabled;
+            let token_valid = state.token_manager.token_status(provider).valid;
+            let models = resolved
+                .values()
+                .filter(|deployments| deployments.iter().any(|d| d.provider_name == provider.name))
+                .count();
+            if routable && token_valid {
+                valid_token = true;
+                if models > 0 {
+                    serving.push(provider.name.as_str());
+                }
+            }
+            let refresh = refresh_status.get(&provider.name);
+            providers.push(ProviderReadiness {
+                name: provider.name.clone(),
+                routable,
+                token_valid,
+                models,
+                refreshed: refresh.is_some_and(|r| r.last_success.is_some() && !r.stale),
+                stale: refresh.is_some_and(|r| r.stale),
+                last_success: refresh.and_then(|r| r.last_success),
+                last_error: refresh.and_then(|r| r.last_error.clone()),
+            });
+        }
+
+        let models = resolved
+            .values()
+            .filter(|deployments| {
+                deployments
+                    .iter()
+                    .any(|d| serving.contains(&d.provider_name.as_str()))
+            })
+            .count();
+        let refreshed = registry.has_refreshed();
+        Self {
+            ready: refreshed && !serving.is_empty(),
+            refreshed,
+            valid_token,
+            models,
+            providers,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        if self.ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+
+    /// The readiness as JSON. Refresh errors may contain upstream URLs and
+    /// responses, so they are only included with `errors`.
+    pub fn to_json(&self, errors: bool) -> Value {
+        let providers: Vec<Value> = self
+            .providers
+            .iter()
+            .map(|provider| {
+                let mut json = json!({
+                    "name": provider.name,
+                    "routable": provider.routable,
+                    "token_valid": provider.token_valid,
+                    "models": provider.models,
+                    "refreshed": provider.refreshed,
+                    "stale": provider.stale,
+                    "last_success": provider.last_success.map(DateTime::<Utc>::from),
+                });
+                if errors {
+                    json["last_error"] = json!(provider.last_error);
+                }
+                json
+            })
+            .collect();
+        json!({
+            "ready": self.ready,
+            "refreshed": self.refreshed,
+            "valid_token": self.valid_token,
+            "models": self.models,
+            "providers": providers,
+        })
+    }
+}
+
+/// Readiness probe. Refresh errors are only served by the admin API.
+pub async fn readiness_check(State(state): State<AppState>) -> impl IntoResponse {
+    let snapshot = state.snapshot();
+    let readiness = Readiness::check(&state, &snapshot).await;
+    (readiness.status(), Json(readiness.to_json(false)))
+}
+
 fn extract_model_from_body(body: &Value) -> Result<String, AppError> {
     body.get("model")
         .and_then(|v| v.as_str())
@@ -73,39 +242,53 @@ fn ensure_model_in_body(body: &mut Value, model: &str) {
     }
 }
 
+/// Split `model:action`; the model name may itself contain a version, e.g.
+/// `gemini-2.5-pro:001:generateContent`.
 fn parse_model_operation(model_operation: &str) -> Result<(String, String), AppError> {
-    let parts: Vec<&str> = model_operation.split(':').collect();
-    if parts.len() != 2 {
-        return Err(AppError::BadRequest(
+    match model_operation.rsplit_once(':') {
+        Some((model, action)) if !model.is_empty() && !action.is_empty() => {
+            Ok((model.to_string(), action.to_string()))
+        }
+        _ => Err(AppError::BadRequest(
             "Invalid model operation format. Expected 'model:action'".to_string(),
-        ));
+        )),
     }
-    Ok((parts[0].to_string(), parts[1].to_string()))
 }
 
+/// Connection details; absent when the router is served without connect info
+type Peer = Option<Extension<ConnectInfo<PeerInfo>>>;
+
 async fn execute_proxy_request(
     state: &AppState,
+    peer: &Peer,
     headers: &HeaderMap,
     body: Value,
     model: &str,
     action: Option<String>,
 ) -> Result<Response, AppError> {
+    let snapshot = state.snapshot();
     let params = ProxyRequestParams {
         headers,
+        client_certificate: peer
+            .as_ref()
+            .and_then(|Extension(ConnectInfo(peer))| peer.client_identity.as_deref()),
         method: Method::POST,
         body,
         model: model.to_string(),
         action,
-        config: &state.config,
+        config: &snapshot.config,
+        authenticator: &snapshot.authenticator,
         token_manager: &state.token_manager,
-        model_registry: &state.model_registry,
-        load_balancer: &state.load_balancer,
+        model_registry: &snapshot.model_registry,
+        load_balancer: &snapshot.load_balancer,
     };
 
     let builder = ProxyRequestBuilder::new(params);
+    let principal = builder.authenticate().await?;
 
-    // Get providers in round-robin order with fallback
-    let providers = state.load_balancer.get_ordered_providers();
+    // Get providers in round-robin order with fallback; deployments are
+    // ordered per provider when its requests are built
+    let providers = snapshot.load_balancer.get_ordered_providers();
     if providers.is_empty() {
         return Err(AppError::Internal(anyhow::anyhow!(
             "No providers available"
@@ -113,12 +296,13 @@ async fn execute_proxy_request(
     }
 
     let mut last_error: Option<AppError> = None;
+    let mut attempts = 0;
 
-    // Try each provider in order until one succeeds or all are exhausted
-    for (i, provider) in providers.iter().enumerate() {
-        // Try to build the request for this provider
-        let proxy = match builder.build_for_provider(provider).await {
-            Ok(proxy) => proxy,
+    // Try each deployment of each provider in order until one succeeds or all are exhausted
+    for provider in providers {
+        // Try to build the requests for this provider's deployments
+        let proxies = match builder.build_for_provider(provider, &principal).await {
+            Ok(proxies) => proxies,
             Err(AppError::ModelNotAvailableOnProvider { model, provider }) => {
                 tracing::debug!(
                     "Model '{}' not available on provider '{}', trying next",
@@ -128,34 +312,22 @@ async fn execute_proxy_request(
                 last_error = Some(AppError::ModelNotAvailableOnProvider { model, provider });
                 continue;
             }
+            Err(e @ AppError::ProviderToken { .. }) => {
+                // One tenant's UAA being down must not fail requests other
+                // providers can serve
+                tracing::warn!("{}, trying next provider", e);
+                last_error = Some(e);
+                continue;
+            }
             Err(e) => {
-                // Non-recoverable error (auth failure, etc.)
+                // Non-recoverable error (invalid request, etc.)
                 return Err(e);
             }
         };
 
-        // Execute the request
-        match proxy.execute(&state.client, &state.config).await {
-            Ok(ProxyExecuteResult::Response(response)) => {
-                if i > 0 {
-                    tracing::info!(
-                        "Request succeeded on provider '{}' after {} fallback(s)",
-                        provider.name,
-                        i
-                    );
-                }
-                return Ok(response);
-            }
-            Ok(ProxyExecuteResult::RateLimited) => {
-                tracing::warn!(
-                    "Provider '{}' returned 429, trying next provider",
-                    provider.name
-                );
-                last_error = Some(AppError::RateLimited(provider.name.clone()));
-                continue;
-            }
+        let client = match http::provider_client(provider) {
+            Ok(client) => client,
             Err(e) => {
-                // Request failed, try next provider
                 tracing::error!(
                     "Request failed on provider '{}': {}, trying next",
                     provider.name,
@@ -164,6 +336,67 @@ async fn execute_proxy_request(
                 last_error = Some(AppError::Internal(e));
                 continue;
             }
+        };
+
+        for proxy in proxies {
+            // Execute the request
+            let in_flight = snapshot.load_balancer.track(&provider.name);
+            let result = proxy.execute(&client, &snapshot.config).await;
+            attempts += 1;
+            match result {
+                Ok(ProxyExecuteResult::Response(response)) => {
+                    if attempts > 1 {
+                        tracing::info!(
+                            "Request succeeded on provider '{}' deployment '{}' after {} fallback(s)",
+                            provider.name,
+                            proxy.deployment_id,
+                            attempts - 1
+                        );
+                    }
+                    return Ok(hold_until_sent(response, in_flight));
+                }
+                Ok(ProxyExecuteResult::RateLimited) => {
+                    tracing::warn!(
+                        "Provider '{}' deployment '{}' returned 429, trying next deployment",
+                        provider.name,
+                        proxy.deployment_id
+                    );
+                    last_error = Some(AppError::RateLimited(provider.name.clone()));
+                }
+                Ok(ProxyExecuteResult::TimedOut) => {
+                    tracing::warn!(
+                        "Provider '{}' deployment '{}' timed out, trying next deployment",
+                        provider.name,
+                        proxy.deployment_id
+                    );
+                    last_error = Some(AppError::UpstreamTimeout(provider.name.clone()));
+                }
+                Ok(ProxyExecuteResult::DeploymentUnavailable) => {
+                    tracing::warn!(
+                        "Provider '{}' deployment '{}' is not available, evicting it and trying next deployment",
+                        provider.name,
+                        proxy.deployment_id
+                    );
+                    snapshot
+                        .model_registry
+                        .evict_deployment(&provider.name, &proxy.deployment_id)
+                        .await;
+                    snapshot
+                        .model_registry
+                        .request_provider_refresh(&provider.name);
+                    last_error = Some(AppError::DeploymentUnavailable(provider.name.clone()));
+                }
+                Err(e) => {
+                    // Request failed, try next deployment
+                    tracing::error!(
+                        "Request failed on provider '{}' deployment '{}': {}, trying next",
+                        provider.name,
+                        proxy.deployment_id,
+                        e
+                    );
+                    last_error = Some(AppError::Internal(e));
+                }
+            }
         }
     }
 
@@ -177,8 +410,19 @@ async fn execute_proxy_request(
     }
 }
 
+/// Keep a request counted as in flight until its response body has been
+/// sent, so that draining a provider waits for streams to finish.
+fn hold_until_sent(response: Response, in_flight: InFlightGuard) -> Response {
+    let (parts, body) = response.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        let _in_flight = &in_flight;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
 pub async fn get_models(State(state): State<AppState>) -> impl IntoResponse {
-    let model_names = state.model_registry.get_available_models().await;
+    let model_names = state.snapshot().model_registry.get_available_models().await;
 
     let model_data: Vec<serde_json::Value> = model_names
         .into_iter()
@@ -199,41 +443,45 @@ pub async fn get_models(State(state): State<AppState>) -> impl IntoResponse {
 
 pub async fn handle_openai_chat(
     State(state): State<AppState>,
+    peer: Peer,
     headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Result<Response, AppError> {
     let model = extract_model_from_body(&body)?;
-    execute_proxy_request(&state, &headers, body, &model, None).await
+    execute_proxy_request(&state, &peer, &headers, body, &model, None).await
 }
 
 pub async fn handle_azure_openai(
     State(state): State<AppState>,
     Path(model): Path<String>,
+    peer: Peer,
     headers: HeaderMap,
     Json(mut body): Json<Value>,
 ) -> Result<Response, AppError> {
     ensure_model_in_body(&mut body, &model);
     let model = extract_model_from_body(&body)?;
-    execute_proxy_request(&state, &headers, body, &model, None).await
+    execute_proxy_request(&state, &peer, &headers, body, &model, None).await
 }
 
 pub async fn handle_claude_messages(
     State(state): State<AppState>,
+    peer: Peer,
     headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Result<Response, AppError> {
     let model = extract_model_from_body(&body)?;
-    execute_proxy_request(&state, &headers, body, &model, None).await
+    execute_proxy_request(&state, &peer, &headers, body, &model, None).await
 }
 
 pub async fn handle_gemini_models(
     State(state): State<AppState>,
     Path(model_operation): Path<String>,
+    peer: Peer,
     headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Result<Response, AppError> {
     let (model, action) = parse_model_operation(&model_operation)?;
-    execute_proxy_request(&state, &headers, body, &model, Some(action)).await
+    execute_proxy_request(&state, &peer, &headers, body, &model, Some(action)).await
 }
 
 #[derive(Debug, Error)]
@@ -244,12 +492,27 @@ pub enum AppError {
     MissingApiKey,
     #[error("Invalid API key")]
     InvalidApiKey,
+    #[error("Invalid bearer token: {0}")]
+    InvalidToken(String),
     #[error("Model '{model}' not available on provider '{provider}'")]
     ModelNotAvailableOnProvider { model: String, provider: String },
     #[error("Rate limited by provider: {0}")]
     RateLimited(String),
     #[error("All providers are rate limited")]
     AllProvidersRateLimited,
+    #[error("Upstream request timed out on provider: {0}")]
+    UpstreamTimeout(String),
+    #[error("Deployment not available on provider: {0}")]
+    DeploymentUnavailable(String),
+    #[error("Failed to get a token for provider '{provider}': {source}")]
+    ProviderToken {
+        provider: String,
+        source: anyhow::Error,
+    },
+    #[error("Admin API is not enabled")]
+    AdminDisabled,
+    #[error("Provider '{0}' not found")]
+    ProviderNotFound(String),
     #[error("Internal server error")]
     Internal(#[from] anyhow::Error),
 }
@@ -263,6 +526,10 @@ impl IntoResponse for AppError {
                 "API key not found in headers".to_string(),
             ),
             AppError::InvalidApiKey => (StatusCode::UNAUTHORIZED, "Invalid API key".to_string()),
+            AppError::InvalidToken(details) => {
+                tracing::debug!("Rejected bearer token: {}", details);
+                (StatusCode::UNAUTHORIZED, "Invalid bearer token".to_string())
+            }
             AppError::ModelNotAvailableOnProvider { model, provider } => (
                 StatusCode::BAD_REQUEST,
                 format!("Model '{}' not available on provider '{}'", model, provider),
@@ -275,6 +542,33 @@ impl IntoResponse for AppError {
                 StatusCode::TOO_MANY_REQUESTS,
                 "All providers are rate limited. Please try again later.".to_string(),
             ),
+            AppError::UpstreamTimeout(provider) => (
+                StatusCode::GATEWAY_TIMEOUT,
+                format!("Upstream request timed out (last provider: {provider})"),
+            ),
+            AppError::ProviderToken { provider, source } => {
+                tracing::error!(
+                    "Failed to get a token for provider '{}': {}",
+                    provider,
+                    source
+                );
+                (
+                    StatusCode::BAD_GATEWAY,
+                    format!("Failed to authenticate with provider '{provider}'"),
+                )
+            }
+            AppError::AdminDisabled => (
+                StatusCode::NOT_FOUND,
+                "Admin API is not enabled. Set admin_api_keys to enable it.".to_string(),
+            ),
+            AppError::ProviderNotFound(provider) => (
+                StatusCode::NOT_FOUND,
+                format!("Provider '{provider}' not found"),
+            ),
+            AppError::DeploymentUnavailable(provider) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("No running deployment of the model (last provider: {provider})"),
+            ),
             AppError::Internal(err) => {
                 tracing::error!("Internal error: {}", err);
                 (
@@ -291,3 +585,292 @@ impl IntoResponse for AppError {
         (status, Json(body)).into_response()
     }
 }
+
+impl From<AuthError> for AppError {
+    fn from(err: AuthError) -> Self {
+        match err {
+            AuthError::InvalidApiKey => AppError::InvalidApiKey,
+            AuthError::InvalidToken { details } => AppError::InvalidToken(details),
+            AuthError::JwksUnavailable { source } => AppError::Internal(source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::TokenManager;
+    use axum::body::to_bytes;
+
+    /// Serve UAA tokens and a gpt-4o and a gemini-2.5-pro deployment with
+    /// the given status. Only gpt-4o chat completions are answered.
+    async fn start_fake_aicore(status: &str) -> String {
+        let deployment = |id: &str, model: &str| {
+            json!({
+                "id": id,
+                "createdAt": "2025-01-01T00:00:00Z",
+                "modifiedAt": "2025-01-01T00:00:00Z",
+                "status": status,
+                "scenarioId": "foundation-models",
+                "configurationId": "c",
+                "details": {"resources": {"backendDetails": {"model": {"name": model}}}}
+            })
+        };
+        let deployments = json!({
+            "count": 2,
+            "resources": [
+                deployment("d-gpt-4o", "gpt-4o"),
+                deployment("d-gemini", "gemini-2.5-pro")
+            ]
+        });
+        let app = Router::new()
+            .route(
+                "/oauth/token",
+                post(|| async { Json(json!({"access_token": "token", "expires_in": 3600})) }),
+            )
+            .route(
+                "/v2/lm/deployments",
+                get(move || async move { Json(deployments) }),
+            )
+            .route(
+                "/v2/inference/deployments/d-gpt-4o/chat/completions",
+                post(|| async { Json(json!({"choices": []})) }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    fn app_state(url: &str) -> AppState {
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+api_keys: [key]
+models:
+  - name: gpt-4o
+providers:
+  - name: eu10
+    uaa_token_url: {url}/oauth/token
+    uaa_client_id: c
+    uaa_client_secret: s
+    genai_api_url: {url}
+"#
+        ))
+        .unwrap();
+        state_from(config)
+    }
+
+    fn state_from(config: Config) -> AppState {
+        let token_manager = TokenManager::new();
+        let snapshot = Snapshot {
+            authenticator: Authenticator::from_config(&config),
+            load_balancer: LoadBalancer::new(
+                config.providers.clone(),
+                config.load_balancing.clone(),
+            ),
+            model_registry: ModelRegistry::from_config(&config, token_manager.clone()),
+            config,
+        };
+        AppState::new(snapshot, token_manager)
+    }
+
+    async fn readiness(state: &AppState) -> (StatusCode, Value) {
+        let response = readiness_check(State(state.clone())).await.into_response();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_token_failure_falls_through_to_next_provider() {
+        let url = start_fake_aicore("RUNNING").await;
+        // Nothing listens on port 1, so the first provider gets no token
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+api_keys: [key]
+load_balancing: fallback
+models:
+  - name: gpt-4o
+providers:
+  - name: broken
+    uaa_token_url: http://127.0.0.1:1/oauth/token
+    uaa_client_id: c
+    uaa_client_secret: s
+    genai_api_url: {url}
+  - name: eu10
+    uaa_token_url: {url}/oauth/token
+    uaa_client_id: c
+    uaa_client_secret: s
+    genai_api_url: {url}
+"#
+        ))
+        .unwrap();
+        let state = state_from(config);
+        state
+            .snapshot()
+            .model_registry
+            .refresh_deployments()
+            .await
+            .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "key".parse().unwrap());
+        let body = json!({"model": "gpt-4o", "messages": []});
+        let response = execute_proxy_request(&state, &None, &headers, body, "gpt-4o", None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Without a provider to fall back to, the token failure is reported
+        state
+            .snapshot()
+            .load_balancer
+            .set_provider_mode("eu10", ProviderMode::Disabled);
+        let body = json!({"model": "gpt-4o", "messages": []});
+        let result = execute_proxy_request(&state, &None, &headers, body, "gpt-4o", None).await;
+        assert!(matches!(
+            result,
+            Err(AppError::ProviderToken { ref provider, .. }) if provider == "broken"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_not_found_path_keeps_deployment() {
+        let url = start_fake_aicore("RUNNING").await;
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+api_keys: [key]
+models:
+  - name: gemini-2.5-pro
+providers:
+  - name: eu10
+    uaa_token_url: {url}/oauth/token
+    uaa_client_id: c
+    uaa_client_secret: s
+    genai_api_url: {url}
+"#
+        ))
+        .unwrap();
+        let state = state_from(config);
+        let snapshot = state.snapshot();
+        snapshot.model_registry.refresh_deployments().await.unwrap();
+
+        // The fake answers 404 for any Gemini action, like an unknown one
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "key".parse().unwrap());
+        let response = execute_proxy_request(
+            &state,
+            &None,
+            &headers,
+            json!({"contents": []}),
+            "gemini-2.5-pro",
+            Some("bogusAction".to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            snapshot
+                .model_registry
+                .get_deployments_for_provider("gemini-2.5-pro", "eu10")
+                .await
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_readiness() {
+        let state = app_state(&start_fake_aicore("RUNNING").await);
+
+        // Nothing refreshed yet
+        let (status, body) = readiness(&state).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["refreshed"], false);
+        assert_eq!(body["valid_token"], false);
+        assert_eq!(body["providers"][0]["name"], "eu10");
+        assert_eq!(body["providers"][0]["refreshed"], false);
+
+        state
+            .snapshot()
+            .model_registry
+            .refresh_deployments()
+            .await
+            .unwrap();
+        let (status, body) = readiness(&state).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["models"], 1);
+        assert_eq!(body["valid_token"], true);
+        assert_eq!(body["providers"][0]["token_valid"], true);
+        assert_eq!(body["providers"][0]["stale"], false);
+        assert!(body["providers"][0]["last_success"].is_string());
+
+        // No routable provider left
+        state
+            .snapshot()
+            .load_balancer
+            .set_provider_mode("eu10", ProviderMode::Disabled);
+        let (status, body) = readiness(&state).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["valid_token"], false);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_needs_models_on_a_provider_with_token() {
+        let running = start_fake_aicore("RUNNING").await;
+        let stopped = start_fake_aicore("STOPPED").await;
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+api_keys: [key]
+models:
+  - name: gpt-4o
+providers:
+  - name: eu10
+    uaa_token_url: {running}/oauth/token
+    uaa_client_id: c
+    uaa_client_secret: s
+    genai_api_url: {running}
+  - name: us10
+    uaa_token_url: {stopped}/oauth/token
+    uaa_client_id: c
+    uaa_client_secret: s
+    genai_api_url: {stopped}
+"#
+        ))
+        .unwrap();
+        let state = state_from(config);
+        let snapshot = state.snapshot();
+        snapshot.model_registry.refresh_deployments().await.unwrap();
+        assert_eq!(readiness(&state).await.0, StatusCode::OK);
+
+        // us10 has a valid token but no models
+        snapshot
+            .load_balancer
+            .set_provider_mode("eu10", ProviderMode::Disabled);
+        let (status, body) = readiness(&state).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["valid_token"], true);
+        assert_eq!(body["models"], 0);
+
+        assert_eq!(body["providers"][1]["name"], "us10");
+        assert_eq!(body["providers"][1]["token_valid"], true);
+        assert_eq!(body["providers"][1]["models"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_fails_without_tokens() {
+        // Nothing listens on port 1, so token and deployment requests fail
+        let state = app_state("http://127.0.0.1:1");
+        state
+            .snapshot()
+            .model_registry
+            .refresh_deployments()
+            .await
+            .unwrap();
+        let (status, body) = readiness(&state).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["refreshed"], false);
+        assert!(body["providers"][0].get("last_error").is_none());
+        assert!(!body.to_string().contains("127.0.0.1"));
+    }
+}
@@ -0,0 +1,152 @@
+//! Declarative request body rules for model quirks, such as parameters that a
+//! model generation renamed or no longer accepts.
+
+use serde_json::{Map, Value};
+use std::sync::LazyLock;
+
+use crate::config::BodyRule;
+use crate::policy::{get_path, remove_path, set_path};
+use crate::registry::glob_matches;
+
+/// Rules shipped with the router. Configured rules with the same name replace them.
+const BUILTIN_RULES: &str = r#"
+- name: gpt-5-max-completion-tokens
+  models: ["gpt-5*"]
+  rename: {max_tokens: max_completion_tokens}
+- name: gpt-5-no-temperature
+  models: ["gpt-5*"]
+  remove: [temperature]
+- name: claude-no-context-management
+  models: ["claude*"]
+  remove: [context_management]
+- name: claude-thinking-no-temperature
+  models: ["claude*"]
+  when_present: thinking
+  remove: [temperature]
+"#;
+
+static BUILTIN: LazyLock<Vec<BodyRule>> =
+    LazyLock::new(|| serde_yaml::from_str(BUILTIN_RULES).expect("Invalid built-in body rules"));
+
+/// The built-in rules.
+pub fn builtin() -> &'static [BodyRule] {
+    &BUILTIN
+}
+
+/// Built-in rules with configured ones of the same name replaced in place,
+/// followed by the remaining configured rules in order.
+pub fn merge_with_builtin(configured: Vec<BodyRule>) -> Vec<BodyRule> {
+    let mut rules = builtin().to_vec();
+    for rule in configured {
+        match rules.iter_mut().find(|existing| existing.name == rule.name) {
+            Some(existing) => *existing = rule,
+            None => rules.push(rule),
+        }
+    }
+    rules
+}
+
+/// Whether a rule applies to requests for `model`.
+pub fn matches(rule: &BodyRule, model: &str) -> bool {
+    rule.enabled
+        && (rule.models.is_empty()
+            || rule
+                .models
+                .iter()
+                .any(|pattern| glob_matches(pattern, model).is_some()))
+}
+
+/// Apply the matching rules to a request body in order.
+pub fn apply(rules: &[BodyRule], body: &mut Value, model: &str) {
+    let Some(obj) = body.as_object_mut() else {
+        return;
+    };
+    for rule in rules.iter().filter(|rule| matches(rule, model)) {
+        apply_rule(rule, obj);
+    }
+}
+
+fn apply_rule(rule: &BodyRule, obj: &mut Map<String, Value>) {
+    if let Some(ref field) = rule.when_present
+        && get_path(obj, field).is_none()
+    {
+        return;
+    }
+    for (from, to) in &rule.rename {
+        if let Some(value) = remove_path(obj, from) {
+            set_path(obj, to, value);
+        }
+    }
+    for field in &rule.remove {
+        remove_path(obj, field);
+    }
+    for (field, value) in &rule.set_default {
+        if get_path(obj, field).is_none() {
+            set_path(obj, field, value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_builtin_rules() {
+        let rules = builtin();
+
+        let mut body = json!({"max_tokens": 100, "temperature": 0.5, "messages": []});
+        apply(rules, &mut body, "gpt-5-mini");
+        assert_eq!(body, json!({"max_completion_tokens": 100, "messages": []}));
+
+        let mut body = json!({"max_tokens": 100, "temperature": 0.5});
+        apply(rules, &mut body, "gpt-4o");
+        assert_eq!(body, json!({"max_tokens": 100, "temperature": 0.5}));
+
+        let mut body = json!({"temperature": 0.5, "context_management": {}});
+        apply(rules, &mut body, "claude-sonnet-4-5");
+        assert_eq!(body, json!({"temperature": 0.5}));
+
+        let mut body = json!({"temperature": 0.5, "thinking": {"type": "enabled"}});
+        apply(rules, &mut body, "claude-sonnet-4-5");
+        assert_eq!(body, json!({"thinking": {"type": "enabled"}}));
+    }
+
+    #[test]
+    fn test_configured_rules_extend_and_replace_builtin() {
+        let configured: Vec<BodyRule> = serde_yaml::from_str(
+            r#"
+- name: gpt-5-no-temperature
+  enabled: false
+- name: o-series
+  models: ["o3*", "o4-mini"]
+  rename: {max_tokens: max_completion_tokens}
+  set_default: {reasoning_effort: medium}
+"#,
+        )
+        .unwrap();
+        let rules = merge_with_builtin(configured);
+        assert_eq!(rules.len(), builtin().len() + 1);
+        assert_eq!(rules[1].name, "gpt-5-no-temperature");
+        assert!(!rules[1].enabled);
+
+        let mut body = json!({"max_tokens": 100, "temperature": 0.5});
+        apply(&rules, &mut body, "gpt-5");
+        assert_eq!(
+            body,
+            json!({"max_completion_tokens": 100, "temperature": 0.5})
+        );
+
+        let mut body = json!({"max_tokens": 100});
+        apply(&rules, &mut body, "o3-mini");
+        assert_eq!(
+            body,
+            json!({"max_completion_tokens": 100, "reasoning_effort": "medium"})
+        );
+
+        let mut body = json!({"reasoning_effort": "high"});
+        apply(&rules, &mut body, "o4-mini");
+        assert_eq!(body, json!({"reasoning_effort": "high"}));
+    }
+}
@@ -0,0 +1,343 @@
+//! Secret indirection for config values.
+//!
+//! Every string value in the config file may use:
+//! - `${VAR}` / `${VAR:-default}`: replaced with the environment variable (`$${` escapes)
+//! - `file:/path`: replaced with the trimmed contents of the file (e.g. a Kubernetes secret mount)
+//! - `enc:v1:<base64>`: AES-256-GCM ciphertext, decrypted with the local secret key file
+//!
+//! Interpolation runs first, so `file:${SECRETS_DIR}/client-secret` works as expected.
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_yaml::Value;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Prefix of values read from a file
+pub const FILE_PREFIX: &str = "file:";
+/// Prefix of encrypted values
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+/// Env var overriding the location of the secret key file
+pub const SECRET_KEY_FILE_ENV: &str = "ACR_SECRET_KEY_FILE";
+/// Top-level config field naming the secret key file
+const SECRET_KEY_FILE_FIELD: &str = "secret_key_file";
+
+const NONCE_LEN: usize = 12;
+
+/// Default key location: `~/.aicore/secret.key`
+pub fn default_key_file() -> Result<PathBuf> {
+    let home = env::var("HOME").context("HOME environment variable not set")?;
+    Ok(Path::new(&home).join(".aicore").join("secret.key"))
+}
+
+/// Generate a new random key, base64 encoded as stored in the key file.
+pub fn generate_key() -> String {
+    BASE64.encode(Aes256Gcm::generate_key(OsRng))
+}
+
+/// Load a key file containing a base64 encoded 256-bit key.
+pub fn load_key(path: &Path) -> Result<Key<Aes256Gcm>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read secret key file: {}", path.display()))?;
+    let bytes = BASE64
+        .decode(content.trim())
+        .with_context(|| format!("Secret key file is not valid base64: {}", path.display()))?;
+    if bytes.len() != 32 {
+        return Err(anyhow::anyhow!(
+            "Secret key file must contain a 256-bit key, found {} bytes: {}",
+            bytes.len(),
+            path.display()
+        ));
+    }
+    Ok(*Key::<Aes256Gcm>::from_slice(&bytes))
+}
+
+/// Encrypt a value into the `enc:v1:` form accepted in config files.
+pub fn encrypt(plaintext: &str, key: &Key<Aes256Gcm>) -> Result<String> {
+    let cipher = Aes256Gcm::new(key);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt value"))?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{ENCRYPTED_PREFIX}{}", BASE64.encode(payload)))
+}
+
+/// Decrypt an `enc:v1:` value.
+pub fn decrypt(value: &str, key: &Key<Aes256Gcm>) -> Result<String> {
+    let encoded = value
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .ok_or_else(|| anyhow::anyhow!("Encrypted value must start with '{ENCRYPTED_PREFIX}'"))?;
+    let payload = BASE64
+        .decode(encoded)
+        .context("Encrypted value is not valid base64")?;
+    if payload.len() <= NONCE_LEN {
+        return Err(anyhow::anyhow!("Encrypted value is too short"));
+    }
+
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    let plaintext = Aes256Gcm::new(key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt value (wrong key or corrupted value)"))?;
+    String::from_utf8(plaintext).context("Decrypted value is not valid UTF-8")
+}
+
+/// Resolves secret references in config values.
+pub struct SecretResolver<'a> {
+    lookup_env: &'a dyn Fn(&str) -> Option<String>,
+    key_file: Option<PathBuf>,
+    key: Option<Key<Aes256Gcm>>,
+}
+
+impl<'a> SecretResolver<'a> {
+    pub fn new(lookup_env: &'a dyn Fn(&str) -> Option<String>, key_file: Option<PathBuf>) -> Self {
+        Self {
+            lookup_env,
+            key_file,
+            key: None,
+        }
+    }
+
+    /// Resolve a single value. `path` names the config field for error messages.
+    pub fn resolve(&mut self, value: &str, path: &str) -> Result<String> {
+        let value = self
+            .interpolate(value)
+            .with_context(|| format!("Failed to resolve '{path}'"))?;
+
+        if let Some(file) = value.strip_prefix(FILE_PREFIX) {
+            let content = std::fs::read_to_string(file)
+                .with_context(|| format!("Failed to read '{path}' from file: {file}"))?;
+            return Ok(content.trim_end_matches(['\r', '\n']).to_string());
+        }
+
+        if value.starts_with(ENCRYPTED_PREFIX) {
+            let key = self.key()?;
+            return decrypt(&value, &key).with_context(|| format!("Failed to decrypt '{path}'"));
+        }
+
+        Ok(value)
+    }
+
+    /// Replace `${VAR}` and `${VAR:-default}` with environment values.
+    fn interpolate(&self, value: &str) -> Result<String> {
+        let mut output = String::with_capacity(value.len());
+        let mut rest = value;
+
+        while let Some(pos) = rest.find('$') {
+            output.push_str(&rest[..pos]);
+            rest = &rest[pos..];
+
+            if let Some(after) = rest.strip_prefix("$${") {
+                output.push_str("${");
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("${") {
+                let end = after
+                    .find('}')
+                    .ok_or_else(|| anyhow::anyhow!("Unterminated '${{' in value"))?;
+                let expr = &after[..end];
+                let (name, default) = match expr.split_once(":-") {
+                    Some((name, default)) => (name, Some(default)),
+                    None => (expr, None),
+                };
+                let resolved = (self.lookup_env)(name)
+                    .or_else(|| default.map(str::to_string))
+                    .ok_or_else(|| anyhow::anyhow!("Environment variable '{name}' is not set"))?;
+                output.push_str(&resolved);
+                rest = &after[end + 1..];
+            } else {
+                output.push('$');
+                rest = &rest[1..];
+            }
+        }
+
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    fn key(&mut self) -> Result<Key<Aes256Gcm>> {
+        if let Some(key) = self.key {
+            return Ok(key);
+        }
+        let path = match &self.key_file {
+            Some(path) => path.clone(),
+            None => default_key_file()?,
+        };
+        let key = load_key(&path)?;
+        self.key = Some(key);
+        Ok(key)
+    }
+
+    /// Resolve every string value in a YAML document in place.
+    pub fn resolve_tree(&mut self, value: &mut Value, path: &str) -> Result<()> {
+        match value {
+            Value::String(s) => {
+                *s = self.resolve(s, path)?;
+            }
+            Value::Sequence(items) => {
+                for (i, item) in items.iter_mut().enumerate() {
+                    self.resolve_tree(item, &format!("{path}[{i}]"))?;
+                }
+            }
+            Value::Mapping(map) => {
+                for (k, v) in map.iter_mut() {
+                    let key = k.as_str().unwrap_or("?");
+                    let child = if path.is_empty() {
+                        key.to_string()
+                    } else {
+                        format!("{path}.{key}")
+                    };
+                    self.resolve_tree(v, &child)?;
+                }
+            }
+            Value::Tagged(tagged) => self.resolve_tree(&mut tagged.value, path)?,
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Resolve secret references throughout a parsed config document.
+///
+/// The key file is taken from `ACR_SECRET_KEY_FILE`, then the top-level
+/// `secret_key_file` field, then `~/.aicore/secret.key`. It is only read when
+/// an encrypted value is present.
+pub fn resolve_config_secrets(document: &mut Value) -> Result<()> {
+    let lookup_env = |name: &str| env::var(name).ok();
+
+    let key_file = match env::var(SECRET_KEY_FILE_ENV) {
+        Ok(path) => Some(PathBuf::from(path)),
+        Err(_) => match document.get(SECRET_KEY_FILE_FIELD).and_then(|v| v.as_str()) {
+            Some(path) => {
+                let path = SecretResolver::new(&lookup_env, None).interpolate(path)?;
+                Some(PathBuf::from(path))
+            }
+            None => None,
+        },
+    };
+
+    SecretResolver::new(&lookup_env, key_file).resolve_tree(document, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn env_from(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> + use<> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_env_interpolation() {
+        let env = env_from(&[("CLIENT_SECRET", "s3cret"), ("HOST", "uaa.example.com")]);
+        let mut resolver = SecretResolver::new(&env, None);
+
+        assert_eq!(resolver.resolve("${CLIENT_SECRET}", "x").unwrap(), "s3cret");
+        assert_eq!(
+            resolver
+                .resolve("https://${HOST}/oauth/token", "x")
+                .unwrap(),
+            "https://uaa.example.com/oauth/token"
+        );
+        assert_eq!(resolver.resolve("${MISSING:-dflt}", "x").unwrap(), "dflt");
+        assert_eq!(resolver.resolve("$${LITERAL}", "x").unwrap(), "${LITERAL}");
+        assert_eq!(resolver.resolve("cost: $5", "x").unwrap(), "cost: $5");
+
+        let err = resolver.resolve("${MISSING}", "providers[0].uaa_client_secret");
+        let msg = format!("{:#}", err.unwrap_err());
+        assert!(msg.contains("providers[0].uaa_client_secret"));
+        assert!(msg.contains("MISSING"));
+    }
+
+    #[test]
+    fn test_file_reference() {
+        let dir = TempDir::new().unwrap();
+        let secret_path = dir.path().join("client-secret");
+        std::fs::write(&secret_path, "from-file\n").unwrap();
+
+        let env = env_from(&[("SECRETS_DIR", dir.path().to_str().unwrap())]);
+        let mut resolver = SecretResolver::new(&env, None);
+
+        assert_eq!(
+            resolver
+                .resolve(&format!("file:{}", secret_path.display()), "x")
+                .unwrap(),
+            "from-file"
+        );
+        assert_eq!(
+            resolver
+                .resolve("file:${SECRETS_DIR}/client-secret", "x")
+                .unwrap(),
+            "from-file"
+        );
+        assert!(resolver.resolve("file:/nonexistent/secret", "x").is_err());
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let key_path = dir.path().join("secret.key");
+        std::fs::write(&key_path, generate_key()).unwrap();
+        let key = load_key(&key_path).unwrap();
+
+        let encrypted = encrypt("top-secret", &key).unwrap();
+        assert!(encrypted.starts_with(ENCRYPTED_PREFIX));
+        assert_ne!(encrypt("top-secret", &key).unwrap(), encrypted);
+
+        let env = env_from(&[]);
+        let mut resolver = SecretResolver::new(&env, Some(key_path));
+        assert_eq!(resolver.resolve(&encrypted, "x").unwrap(), "top-secret");
+
+        // A different key cannot decrypt the value
+        let other_path = dir.path().join("other.key");
+        std::fs::write(&other_path, generate_key()).unwrap();
+        let mut resolver = SecretResolver::new(&env, Some(other_path));
+        assert!(resolver.resolve(&encrypted, "x").is_err());
+    }
+
+    #[test]
+    fn test_invalid_key_file() {
+        let dir = TempDir::new().unwrap();
+        let key_path = dir.path().join("short.key");
+        std::fs::write(&key_path, BASE64.encode([0u8; 16])).unwrap();
+
+        assert!(load_key(&key_path).is_err());
+    }
+
+    #[test]
+    fn test_resolve_tree() {
+        let env = env_from(&[("SECRET_A", "a"), ("KEY_1", "k1")]);
+        let mut resolver = SecretResolver::new(&env, None);
+
+        let mut document: Value = serde_yaml::from_str(
+            r#"
+port: 8080
+api_keys: ["${KEY_1}", plain]
+providers:
+  - name: p1
+    uaa_client_secret: ${SECRET_A}
+"#,
+        )
+        .unwrap();
+
+        resolver.resolve_tree(&mut document, "").unwrap();
+
+        assert_eq!(document["api_keys"][0].as_str(), Some("k1"));
+        assert_eq!(document["api_keys"][1].as_str(), Some("plain"));
+        assert_eq!(
+            document["providers"][0]["uaa_client_secret"].as_str(),
+            Some("a")
+        );
+        assert_eq!(document["port"].as_u64(), Some(8080));
+    }
+}
@@ -0,0 +1,498 @@
+//! TLS termination for listeners, with optional client certificate verification.
+//!
+//! Handshakes run concurrently off the accept loop, so a slow client cannot
+//! stall other connections. Certificate files are watched and the server
+//! config is swapped in place when they change; established connections are
+//! not affected.
+
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+use tracing::{debug, error, info, warn};
+use x509_parser::extensions::GeneralName;
+
+use crate::config::TlsConfig;
+use crate::constants::{reload::DEBOUNCE_MS, tls::HANDSHAKE_TIMEOUT_SECS};
+use crate::reload::watch_file;
+
+/// Completed handshakes waiting to be picked up by the server
+const ACCEPT_QUEUE_SIZE: usize = 64;
+
+/// Build a rustls server config from the certificate files of a listener.
+pub fn load_server_config(tls: &TlsConfig) -> Result<Arc<ServerConfig>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let certs = load_certs(&tls.cert)?;
+    let key = PrivateKeyDer::from_pem_file(&tls.key)
+        .with_context(|| format!("Failed to read private key from {}", tls.key))?;
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .context("Failed to configure TLS protocol versions")?;
+    let builder = match tls.client_ca {
+        Some(ref client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(client_ca)? {
+                roots
+                    .add(cert)
+                    .with_context(|| format!("Invalid CA certificate in {client_ca}"))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if tls.client_cert_required {
+                verifier
+            } else {
+                verifier.allow_unauthenticated()
+            };
+            builder.with_client_cert_verifier(
+                verifier
+                    .build()
+                    .context("Failed to build client certificate verifier")?,
+            )
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .with_context(|| format!("Invalid certificate or private key ({})", tls.cert))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .with_context(|| format!("Failed to read certificates from {path}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid PEM in {path}"))?;
+    if certs.is_empty() {
+        return Err(anyhow::anyhow!("No certificates found in {path}"));
+    }
+    Ok(certs)
+}
+
+/// Identity of a client certificate: the subject common name, else the first
+/// DNS subject alternative name, else the SHA-256 fingerprint.
+pub fn client_identity(cert: &CertificateDer<'_>) -> String {
+    if let Ok((_, parsed)) = x509_parser::parse_x509_certificate(cert.as_ref()) {
+        if let Some(cn) = parsed
+            .subject()
+            .iter_common_name()
+            .find_map(|cn| cn.as_str().ok())
+            .filter(|cn| !cn.is_empty())
+        {
+            return cn.to_string();
+        }
+        if let Ok(Some(san)) = parsed.subject_alternative_name()
+            && let Some(dns) = san.value.general_names.iter().find_map(|name| match name {
+                GeneralName::DNSName(dns) => Some(dns),
+                _ => None,
+            })
+        {
+            return dns.to_string();
+        }
+    }
+
+    let digest = Sha256::digest(cert.as_ref());
+    let fingerprint: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256:{fingerprint}")
+}
+
+/// Server config of a listener, reloaded when its certificate files change.
+#[derive(Clone)]
+pub struct TlsContext {
+    server_config: Arc<ArcSwap<ServerConfig>>,
+    // Reloading stops once the last clone is dropped
+    _watchers: Arc<Vec<notify::RecommendedWatcher>>,
+}
+
+impl TlsContext {
+    /// Load the certificates and start watching them for rotation.
+    pub fn load(tls: &TlsConfig) -> Result<Self> {
+        let server_config = Arc::new(ArcSwap::new(load_server_config(tls)?));
+
+        let (tx, mut rx) = mpsc::channel::<()>(1);
+        let mut paths: Vec<&str> = vec![&tls.cert, &tls.key];
+        paths.extend(tls.client_ca.as_deref());
+        let mut watched = HashSet::new();
+        let mut watchers = Vec::new();
+        for path in paths {
+            if watched.insert(path) {
+                watchers.push(watch_file(&PathBuf::from(path), tx.clone())?);
+            }
+        }
+
+        let tls = tls.clone();
+        let target = server_config.clone();
+        tokio::spawn(async move {
+            while rx.recv().await.is_some() {
+                // Certificate and key are usually replaced one after the other
+                tokio::time::sleep(Duration::from_millis(DEBOUNCE_MS)).await;
+                while rx.try_recv().is_ok() {}
+
+                match load_server_config(&tls) {
+                    Ok(config) => {
+                        target.store(config);
+                        info!("Reloaded TLS certificate {}", tls.cert);
+                    }
+                    Err(e) => error!(
+                        "Failed to reload TLS certificate, keeping current one: {:#}",
+                        e
+                    ),
+                }
+            }
+        });
+
+        Ok(Self {
+            server_config,
+            _watchers: Arc::new(watchers),
+        })
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.server_config.load_full())
+    }
+}
+
+/// Remote address and verified client certificate identity of a connection
+#[derive(Debug, Clone)]
+pub struct TlsPeer {
+    pub addr: SocketAddr,
+    pub client_identity: Option<String>,
+}
+
+/// TCP listener that hands out connections after a successful TLS handshake.
+pub struct TlsListener {
+    connections: mpsc::Receiver<(TlsStream<TcpStream>, TlsPeer)>,
+    local_addr: SocketAddr,
+    _context: TlsContext,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, context: TlsContext) -> Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let (tx, connections) = mpsc::channel(ACCEPT_QUEUE_SIZE);
+        tokio::spawn(accept_loop(listener, context.clone(), tx));
+        Ok(Self {
+            connections,
+            local_addr,
+            _context: context,
+        })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = TlsPeer;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.connections.recv().await {
+            Some(connection) => connection,
+            // The accept loop only ends once this listener is dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(TlsPeer {
+            addr: self.local_addr,
+            client_identity: None,
+        })
+    }
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    context: TlsContext,
+    tx: mpsc::Sender<(TlsStream<TcpStream>, TlsPeer)>,
+) {
+    loop {
+        let (stream, addr) = tokio::select! {
+            _ = tx.closed() => return,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) if is_connection_error(&e) => continue,
+                Err(e) => {
+                    // Usually running out of file descriptors; give it a moment
+                    warn!("Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+        };
+
+        let acceptor = context.acceptor();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let handshake = tokio::time::timeout(
+                Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
+                acceptor.accept(stream),
+            );
+            let stream = match handshake.await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    debug!("TLS handshake with {} failed: {}", addr, e);
+                    return;
+                }
+                Err(_) => {
+                    debug!("TLS handshake with {} timed out", addr);
+                    return;
+                }
+            };
+
+            let client_identity = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(client_identity);
+            let _ = tx
+                .send((
+                    stream,
+                    TlsPeer {
+                        addr,
+                        client_identity,
+                    },
+                ))
+                .await;
+        });
+    }
+}
+
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::serve::Listener;
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, Issuer, KeyPair};
+    use rustls::pki_types::ServerName;
+    use std::fs;
+    use tempfile::TempDir;
+    use tokio_rustls::TlsConnector;
+
+    struct Pki {
+        dir: TempDir,
+        ca: rcgen::Certificate,
+        issuer: Issuer<'static, KeyPair>,
+    }
+
+    impl Pki {
+        fn new() -> Self {
+            let mut params = CertificateParams::new(Vec::new()).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "acr test CA");
+            let key = KeyPair::generate().unwrap();
+            let ca = params.self_signed(&key).unwrap();
+            Self {
+                dir: TempDir::new().unwrap(),
+                ca,
+                issuer: Issuer::new(params, key),
+            }
+        }
+
+        /// Issue a certificate and return it with its key.
+        fn issue(
+            &self,
+            common_name: Option<&str>,
+            dns_names: &[&str],
+        ) -> (rcgen::Certificate, KeyPair) {
+            let names: Vec<String> = dns_names.iter().map(|n| n.to_string()).collect();
+            let mut params = CertificateParams::new(names).unwrap();
+            params.distinguished_name = rcgen::DistinguishedName::new();
+            if let Some(cn) = common_name {
+                params.distinguished_name.push(DnType::CommonName, cn);
+            }
+            let key = KeyPair::generate().unwrap();
+            (params.signed_by(&key, &self.issuer).unwrap(), key)
+        }
+
+        fn write(&self, name: &str, contents: &str) -> String {
+            let path = self.dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            path.to_string_lossy().to_string()
+        }
+
+        fn server_tls(&self, client_ca: bool, client_cert_required: bool) -> TlsConfig {
+            let (cert, key) = self.issue(Some("acr"), &["localhost"]);
+            TlsConfig {
+                cert: self.write("server.crt", &cert.pem()),
+                key: self.write("server.key", &key.serialize_pem()),
+                client_ca: client_ca.then(|| self.write("ca.pem", &self.ca.pem())),
+                client_cert_required,
+            }
+        }
+
+        fn connector(&self, client_cert: Option<(rcgen::Certificate, KeyPair)>) -> TlsConnector {
+            let mut roots = RootCertStore::empty();
+            roots.add(self.ca.der().clone()).unwrap();
+            let builder = rustls::ClientConfig::builder_with_provider(Arc::new(
+                rustls::crypto::ring::default_provider(),
+            ))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+            let config = match client_cert {
+                Some((cert, key)) => builder
+                    .with_client_auth_cert(
+                        vec![cert.der().clone()],
+                        PrivateKeyDer::try_from(key.serialize_der()).unwrap(),
+                    )
+                    .unwrap(),
+                None => builder.with_no_client_auth(),
+            };
+            TlsConnector::from(Arc::new(config))
+        }
+    }
+
+    async fn listen(tls: &TlsConfig) -> TlsListener {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        TlsListener::new(listener, TlsContext::load(tls).unwrap()).unwrap()
+    }
+
+    /// Connect and return the peer seen by the server, if the handshake succeeded.
+    async fn connect(listener: &mut TlsListener, connector: TlsConnector) -> Option<TlsPeer> {
+        let addr = listener.local_addr().unwrap().addr;
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let server_name = ServerName::try_from("localhost").unwrap();
+            let tls = connector.connect(server_name, stream).await;
+            // Keep the connection open while the server picks it up
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            drop(tls);
+        });
+        let accepted = tokio::time::timeout(Duration::from_secs(1), listener.accept()).await;
+        client.abort();
+        accepted.ok().map(|(_, peer)| peer)
+    }
+
+    #[test]
+    fn test_client_identity() {
+        let pki = Pki::new();
+        let (cert, _) = pki.issue(Some("billing-service"), &["billing.internal"]);
+        assert_eq!(client_identity(cert.der()), "billing-service");
+
+        let (cert, _) = pki.issue(None, &["billing.internal"]);
+        assert_eq!(client_identity(cert.der()), "billing.internal");
+
+        let (cert, _) = pki.issue(None, &[]);
+        let identity = client_identity(cert.der());
+        assert!(identity.starts_with("sha256:"));
+        assert_eq!(identity.len(), "sha256:".len() + 64);
+    }
+
+    #[test]
+    fn test_load_server_config() {
+        let pki = Pki::new();
+        let tls = pki.server_tls(true, true);
+        assert_eq!(
+            load_server_config(&tls).unwrap().alpn_protocols,
+            vec![b"http/1.1".to_vec()]
+        );
+
+        let missing = TlsConfig {
+            cert: pki
+                .dir
+                .path()
+                .join("missing.crt")
+                .to_string_lossy()
+                .to_string(),
+            ..tls.clone()
+        };
+        assert!(load_server_config(&missing).is_err());
+
+        // Key that does not belong to the certificate
+        let (_, other_key) = pki.issue(Some("other"), &[]);
+        let mismatched = TlsConfig {
+            key: pki.write("other.key", &other_key.serialize_pem()),
+            ..tls
+        };
+        assert!(load_server_config(&mismatched).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_required() {
+        let pki = Pki::new();
+        let mut listener = listen(&pki.server_tls(true, true)).await;
+
+        let client_cert = pki.issue(Some("billing-service"), &[]);
+        let peer = connect(&mut listener, pki.connector(Some(client_cert)))
+            .await
+            .expect("handshake with client certificate");
+        assert_eq!(peer.client_identity.as_deref(), Some("billing-service"));
+
+        assert!(connect(&mut listener, pki.connector(None)).await.is_none());
+
+        // Certificates from another CA are rejected
+        let other = Pki::new();
+        let foreign_cert = other.issue(Some("intruder"), &[]);
+        assert!(
+            connect(&mut listener, pki.connector(Some(foreign_cert)))
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_optional() {
+        let pki = Pki::new();
+        let mut listener = listen(&pki.server_tls(true, false)).await;
+
+        let peer = connect(&mut listener, pki.connector(None))
+            .await
+            .expect("handshake without client certificate");
+        assert_eq!(peer.client_identity, None);
+
+        let client_cert = pki.issue(Some("billing-service"), &[]);
+        let peer = connect(&mut listener, pki.connector(Some(client_cert)))
+            .await
+            .expect("handshake with client certificate");
+        assert_eq!(peer.client_identity.as_deref(), Some("billing-service"));
+    }
+
+    #[tokio::test]
+    async fn test_certificate_rotation() {
+        let pki = Pki::new();
+        let tls = pki.server_tls(false, true);
+        let context = TlsContext::load(&tls).unwrap();
+        let original = context.server_config.load_full();
+
+        // A broken certificate is not picked up
+        fs::write(&tls.cert, "not a certificate").unwrap();
+        tokio::time::sleep(Duration::from_millis(DEBOUNCE_MS + 500)).await;
+        assert!(Arc::ptr_eq(&original, &context.server_config.load_full()));
+
+        let (cert, key) = pki.issue(Some("acr"), &["localhost"]);
+        fs::write(&tls.key, key.serialize_pem()).unwrap();
+        fs::write(&tls.cert, cert.pem()).unwrap();
+        let mut rotated = false;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            if !Arc::ptr_eq(&original, &context.server_config.load_full()) {
+                rotated = true;
+                break;
+            }
+        }
+        assert!(rotated, "certificate was not reloaded");
+    }
+}
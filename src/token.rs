@@ -2,13 +2,14 @@
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use reqwest::Client;
 use serde::Deserialize;
-use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::config::Provider;
+use crate::constants::token::*;
+use crate::http;
 
 #[derive(Debug, Deserialize)]
 struct TokenResponse {
@@ -20,138 +21,332 @@ struct TokenResponse {
 struct TokenInfo {
     token: String,
     expires_at: DateTime<Utc>,
+    /// When the token was fetched
+    fetched_at: Instant,
+    /// When the background refresher should replace the token
+    refresh_at: Instant,
 }
 
 impl TokenInfo {
+    fn new(token: String, expires_in: u64) -> Self {
+        let fetched_at = Instant::now();
+        // Refresh after three quarters of the usable lifetime, well before `is_valid` turns false
+        let usable_secs = expires_in.saturating_sub(EXPIRY_MARGIN_SECS as u64);
+        let refresh_at = fetched_at + Duration::from_millis(usable_secs * 750);
+        Self {
+            token,
+            expires_at: Utc::now() + chrono::Duration::seconds(expires_in as i64),
+            fetched_at,
+            refresh_at,
+        }
+    }
+
     fn is_valid(&self) -> bool {
-        Utc::now() + chrono::Duration::seconds(60) < self.expires_at
+        Utc::now() + chrono::Duration::seconds(EXPIRY_MARGIN_SECS) < self.expires_at
     }
 }
 
+/// Cache entry for one set of provider credentials
+#[derive(Debug, Default)]
+struct TokenEntry {
+    token: Option<TokenInfo>,
+    /// Held while a refresh is in flight, so concurrent callers share one token request
+    flight: Arc<tokio::sync::Mutex<()>>,
+    consecutive_failures: u32,
+    /// No new token request is made before this instant after a failure
+    retry_at: Option<Instant>,
+    last_error: Option<String>,
+    last_used: Option<Instant>,
+    refresher_running: bool,
+}
+
+type TokenCache = Arc<Mutex<HashMap<String, TokenEntry>>>;
+
+/// State of the cached token of a provider, without the token itself
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenStatus {
+    /// Expiry of the cached token, if one has been fetched
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Whether the cached token can still be used
+    pub valid: bool,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
 /// Token manager that handles OAuth tokens for multiple providers.
-#[derive(Debug, Clone)]
+///
+/// Concurrent requests for the same credentials share a single token request, and
+/// tokens that are in use are refreshed in the background before they expire.
+#[derive(Debug, Clone, Default)]
 pub struct TokenManager {
-    /// Set of valid API keys for request authentication
-    api_keys: HashSet<String>,
     /// Cached tokens keyed by provider credentials hash
-    tokens: Arc<RwLock<HashMap<String, TokenInfo>>>,
-    /// HTTP client for token requests
-    client: Client,
+    tokens: TokenCache,
 }
 
 impl TokenManager {
-    /// Create a new token manager with the given API keys.
-    pub fn new(api_keys: Vec<String>) -> Self {
-        Self {
-            api_keys: api_keys.into_iter().collect(),
-            tokens: Arc::new(RwLock::new(HashMap::new())),
-            client: Client::new(),
+    /// Create a new token manager with an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get an OAuth token for a specific provider.
+    /// Callers are expected to have authenticated the inbound request already.
+    pub async fn get_token(&self, provider: &Provider) -> Result<String> {
+        let token_key = token_key(provider);
+
+        // Check cache first
+        let flight = {
+            let mut tokens = self.tokens.lock().unwrap();
+            let entry = tokens.entry(token_key.clone()).or_default();
+            entry.last_used = Some(Instant::now());
+            if let Some(token) = self.cached_token(entry, &token_key, provider)? {
+                return Ok(token);
+            }
+            entry.flight.clone()
+        };
+
+        // Only one caller refreshes; the others wait here and then reuse its result
+        let _guard = flight.lock().await;
+        {
+            let mut tokens = self.tokens.lock().unwrap();
+            let entry = tokens.entry(token_key.clone()).or_default();
+            if let Some(token) = self.cached_token(entry, &token_key, provider)? {
+                return Ok(token);
+            }
         }
+
+        let new_token = refresh_entry(&self.tokens, &token_key, provider).await?;
+        self.ensure_refresher(&token_key, provider);
+        Ok(new_token.token)
     }
 
-    /// Check if an API key is valid.
-    /// The special "internal" key is always valid for internal operations.
-    pub fn is_valid_api_key(&self, api_key: &str) -> bool {
-        api_key == "internal" || self.api_keys.contains(api_key)
+    /// The state of a provider's cached token. Does not fetch a token.
+    pub fn token_status(&self, provider: &Provider) -> TokenStatus {
+        let tokens = self.tokens.lock().unwrap();
+        let Some(entry) = tokens.get(&token_key(provider)) else {
+            return TokenStatus::default();
+        };
+        TokenStatus {
+            expires_at: entry.token.as_ref().map(|t| t.expires_at),
+            valid: entry.token.as_ref().is_some_and(TokenInfo::is_valid),
+            consecutive_failures: entry.consecutive_failures,
+            last_error: entry.last_error.clone(),
+        }
     }
 
-    /// Get an OAuth token for a specific provider.
-    /// Returns None if the API key is invalid.
-    pub async fn get_token_for_provider(
+    /// Return the cached token if still valid, or fail fast while a previous failure backs off.
+    fn cached_token(
         &self,
-        api_key: &str,
+        entry: &mut TokenEntry,
+        token_key: &str,
         provider: &Provider,
     ) -> Result<Option<String>> {
-        if !self.is_valid_api_key(api_key) {
-            return Ok(None);
+        if let Some(token_info) = &entry.token
+            && token_info.is_valid()
+        {
+            let token = token_info.token.clone();
+            if !entry.refresher_running {
+                entry.refresher_running = true;
+                self.spawn_refresher(token_key.to_string(), provider.clone());
+            }
+            return Ok(Some(token));
         }
 
-        let token_key = format!(
-            "{}:{}:{}",
-            provider.uaa_token_url, provider.uaa_client_id, provider.uaa_client_secret
-        );
-
-        // Check cache first
+        if let Some(retry_at) = entry.retry_at
+            && retry_at > Instant::now()
         {
-            let tokens = self.tokens.read().await;
-            if let Some(token_info) = tokens.get(&token_key)
-                && token_info.is_valid()
-            {
-                return Ok(Some(token_info.token.clone()));
-            }
+            return Err(anyhow::anyhow!(
+                "Token refresh for provider '{}' is backing off for {}s after error: {}",
+                provider.name,
+                retry_at.saturating_duration_since(Instant::now()).as_secs() + 1,
+                entry.last_error.as_deref().unwrap_or("unknown")
+            ));
         }
 
-        // Refresh token
-        let new_token = self
-            .refresh_token(
-                &provider.uaa_token_url,
-                &provider.uaa_client_id,
-                &provider.uaa_client_secret,
-            )
-            .await?;
+        Ok(None)
+    }
 
-        // Store in cache
+    fn ensure_refresher(&self, token_key: &str, provider: &Provider) {
+        let mut tokens = self.tokens.lock().unwrap();
+        if let Some(entry) = tokens.get_mut(token_key)
+            && !entry.refresher_running
         {
-            let mut tokens = self.tokens.write().await;
-            tokens.insert(token_key, new_token.clone());
+            entry.refresher_running = true;
+            self.spawn_refresher(token_key.to_string(), provider.clone());
         }
+    }
+
+    /// Refresh the token proactively before it expires, for as long as it keeps being used.
+    /// The task holds only a weak reference to the cache and ends with the token manager.
+    fn spawn_refresher(&self, token_key: String, provider: Provider) {
+        let cache = Arc::downgrade(&self.tokens);
+
+        tokio::spawn(async move {
+            loop {
+                let wake_at = {
+                    let Some(tokens) = cache.upgrade() else {
+                        return;
+                    };
+                    let mut tokens = tokens.lock().unwrap();
+                    let Some(entry) = tokens.get_mut(&token_key) else {
+                        return;
+                    };
+                    match (entry.retry_at, &entry.token) {
+                        (Some(retry_at), _) => retry_at,
+                        (None, Some(token_info)) => token_info.refresh_at,
+                        (None, None) => {
+                            entry.refresher_running = false;
+                            return;
+                        }
+                    }
+                };
 
-        Ok(Some(new_token.token))
+                tokio::time::sleep_until(wake_at.into()).await;
+
+                let Some(tokens) = cache.upgrade() else {
+                    return;
+                };
+                let flight = {
+                    let mut guard = tokens.lock().unwrap();
+                    let Some(entry) = guard.get_mut(&token_key) else {
+                        return;
+                    };
+                    // Stop refreshing credentials nobody used since the last token was fetched
+                    let fetched_at = entry.token.as_ref().map(|t| t.fetched_at);
+                    if entry.last_used.is_none_or(|used| Some(used) < fetched_at) {
+                        tracing::debug!(
+                            "Stopping background token refresh for provider '{}' (idle)",
+                            provider.name
+                        );
+                        entry.refresher_running = false;
+                        return;
+                    }
+                    entry.flight.clone()
+                };
+
+                let _guard = flight.lock().await;
+                if refresh_entry(&tokens, &token_key, &provider).await.is_ok() {
+                    tracing::debug!(
+                        "Proactively refreshed token for provider '{}'",
+                        provider.name
+                    );
+                }
+            }
+        });
     }
+}
 
-    async fn refresh_token(
-        &self,
-        url: &str,
-        client_id: &str,
-        client_secret: &str,
-    ) -> Result<TokenInfo> {
-        let params = [("grant_type", "client_credentials")];
-
-        let response = self
-            .client
-            .post(url)
-            .form(&params)
-            .basic_auth(client_id, Some(client_secret))
-            .send()
-            .await
-            .context("Failed to send token request")?;
-
-        let status = response.status();
-        let headers = response.headers().clone();
-
-        if !status.is_success() {
-            let text = response.text().await.unwrap_or_default();
-            tracing::error!(
-                "OAuth token request failed - Status: {}, Headers: {:?}, Body: {}",
-                status,
-                headers,
-                text
+/// Cache key for a provider's UAA credentials
+fn token_key(provider: &Provider) -> String {
+    match &provider.certificate {
+        Some(certificate) => format!(
+            "{}:{}:{}",
+            provider.uaa_token_url, provider.uaa_client_id, certificate
+        ),
+        None => format!(
+            "{}:{}:{}",
+            provider.uaa_token_url, provider.uaa_client_id, provider.uaa_client_secret
+        ),
+    }
+}
+
+/// Fetch a new token and record the outcome in the cache entry.
+/// On failure the previous token is kept and further attempts back off exponentially.
+async fn refresh_entry(
+    tokens: &TokenCache,
+    token_key: &str,
+    provider: &Provider,
+) -> Result<TokenInfo> {
+    let result = request_token(provider).await;
+
+    let mut tokens = tokens.lock().unwrap();
+    let entry = tokens.entry(token_key.to_string()).or_default();
+    match result {
+        Ok(token_info) => {
+            entry.token = Some(token_info.clone());
+            entry.consecutive_failures = 0;
+            entry.retry_at = None;
+            entry.last_error = None;
+            Ok(token_info)
+        }
+        Err(e) => {
+            entry.consecutive_failures += 1;
+            let exponent = entry.consecutive_failures.saturating_sub(1).min(16);
+            let backoff_secs =
+                (REFRESH_BACKOFF_BASE_SECS << exponent).min(REFRESH_BACKOFF_MAX_SECS);
+            entry.retry_at = Some(Instant::now() + Duration::from_secs(backoff_secs));
+            entry.last_error = Some(e.to_string());
+            tracing::warn!(
+                "Token refresh for provider '{}' failed ({} consecutive), retrying in {}s: {}",
+                provider.name,
+                entry.consecutive_failures,
+                backoff_secs,
+                e
             );
-            return Err(anyhow::anyhow!(
-                "Token request failed with status {}: {}",
-                status,
-                text
-            ));
+            Err(e)
+        }
+    }
+}
+
+/// Request a client-credentials token from UAA.
+/// x509 credentials authenticate with mutual TLS and send only the client id;
+/// otherwise the client id and secret are sent as basic auth.
+async fn request_token(provider: &Provider) -> Result<TokenInfo> {
+    let client_id = provider.uaa_client_id.as_str();
+    let timeouts = provider.timeouts.resolve();
+
+    let request = match (&provider.certificate, &provider.key) {
+        (Some(certificate), Some(key)) => {
+            http::mtls_client(&provider.http, timeouts.connect, certificate, key)?
+                .post(&provider.uaa_token_url)
+                .form(&[
+                    ("grant_type", "client_credentials"),
+                    ("client_id", client_id),
+                ])
         }
+        _ => http::provider_client(provider)?
+            .post(&provider.uaa_token_url)
+            .form(&[("grant_type", "client_credentials")])
+            .basic_auth(client_id, Some(&provider.uaa_client_secret)),
+    };
 
-        let token_response: TokenResponse = response
-            .json()
-            .await
-            .context("Failed to parse token response")?;
+    let response = http::send(request.timeout(timeouts.total), timeouts.first_byte)
+        .await
+        .context("Failed to send token request")?;
 
-        let expires_at = Utc::now() + chrono::Duration::seconds(token_response.expires_in as i64);
+    let status = response.status();
+    let headers = response.headers().clone();
 
-        tracing::debug!(
-            "Token refreshed for client id: {} (expires in {}s)",
-            client_id,
-            token_response.expires_in
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        tracing::error!(
+            "OAuth token request failed - Status: {}, Headers: {:?}, Body: {}",
+            status,
+            headers,
+            text
         );
-
-        Ok(TokenInfo {
-            token: token_response.access_token,
-            expires_at,
-        })
+        return Err(anyhow::anyhow!(
+            "Token request failed with status {}: {}",
+            status,
+            text
+        ));
     }
+
+    let token_response: TokenResponse = response
+        .json()
+        .await
+        .context("Failed to parse token response")?;
+
+    tracing::debug!(
+        "Token refreshed for client id: {} (expires in {}s)",
+        client_id,
+        token_response.expires_in
+    );
+
+    Ok(TokenInfo::new(
+        token_response.access_token,
+        token_response.expires_in,
+    ))
 }
 
 // Keep the old OAuthConfig for backward compatibility during migration
@@ -162,3 +357,171 @@ pub struct OAuthConfig {
     pub client_id: String,
     pub client_secret: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+    use serde_json::{Value, json};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    #[derive(Clone, Default)]
+    struct FakeUaa {
+        calls: Arc<AtomicUsize>,
+        failing: Arc<AtomicBool>,
+        expires_in: u64,
+    }
+
+    async fn issue_token(State(uaa): State<FakeUaa>) -> (StatusCode, Json<Value>) {
+        let n = uaa.calls.fetch_add(1, Ordering::SeqCst) + 1;
+        // Simulate UAA latency so concurrent callers overlap
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        if uaa.failing.load(Ordering::SeqCst) {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(json!({"error": "unavailable"})),
+            );
+        }
+        (
+            StatusCode::OK,
+            Json(json!({"access_token": format!("token-{n}"), "expires_in": uaa.expires_in})),
+        )
+    }
+
+    async fn start_fake_uaa(expires_in: u64) -> (FakeUaa, Provider) {
+        let uaa = FakeUaa {
+            expires_in,
+            ..Default::default()
+        };
+        let app = Router::new()
+            .route("/oauth/token", post(issue_token))
+            .with_state(uaa.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let provider = Provider {
+            name: "fake".to_string(),
+            uaa_token_url: format!("http://{addr}/oauth/token"),
+            uaa_client_id: "client".to_string(),
+            uaa_client_secret: "secret".to_string(),
+            certificate: None,
+            key: None,
+            genai_api_url: format!("http://{addr}"),
+            resource_group: "default".to_string(),
+            resource_groups: Vec::new(),
+            weight: 1,
+            enabled: true,
+            http: Default::default(),
+            timeouts: Default::default(),
+        };
+        (uaa, provider)
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_share_one_refresh() {
+        let (uaa, provider) = start_fake_uaa(3600).await;
+        let manager = TokenManager::new();
+
+        let requests = (0..32).map(|_| manager.get_token(&provider));
+        let tokens = futures::future::join_all(requests).await;
+
+        assert!(tokens.iter().all(|t| t.as_deref().unwrap() == "token-1"));
+        assert_eq!(uaa.calls.load(Ordering::SeqCst), 1);
+
+        // Cached afterwards
+        assert_eq!(manager.get_token(&provider).await.unwrap(), "token-1");
+        assert_eq!(uaa.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_background_refresh_before_expiry() {
+        // 62s lifetime leaves 2s of usable time; the refresh is due after 1.5s
+        let (uaa, provider) = start_fake_uaa(EXPIRY_MARGIN_SECS as u64 + 2).await;
+        let manager = TokenManager::new();
+
+        assert_eq!(manager.get_token(&provider).await.unwrap(), "token-1");
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        // Still in use, so the refresher keeps it warm
+        assert_eq!(manager.get_token(&provider).await.unwrap(), "token-1");
+
+        tokio::time::sleep(Duration::from_millis(1300)).await;
+        assert_eq!(uaa.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(manager.get_token(&provider).await.unwrap(), "token-2");
+    }
+
+    #[tokio::test]
+    async fn test_idle_token_is_not_refreshed() {
+        let (uaa, provider) = start_fake_uaa(EXPIRY_MARGIN_SECS as u64 + 1).await;
+        let manager = TokenManager::new();
+
+        manager.get_token(&provider).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+
+        assert_eq!(uaa.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_backs_off() {
+        let (uaa, provider) = start_fake_uaa(3600).await;
+        uaa.failing.store(true, Ordering::SeqCst);
+        let manager = TokenManager::new();
+
+        let requests = (0..8).map(|_| manager.get_token(&provider));
+        let results = futures::future::join_all(requests).await;
+        assert!(results.iter().all(|r| r.is_err()));
+        assert_eq!(uaa.calls.load(Ordering::SeqCst), 1);
+
+        // Within the backoff window no new token request is made
+        let err = manager.get_token(&provider).await.unwrap_err();
+        assert!(err.to_string().contains("backing off"));
+        assert_eq!(uaa.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_valid_token_served_while_refresh_fails() {
+        let (uaa, provider) = start_fake_uaa(EXPIRY_MARGIN_SECS as u64 + 2).await;
+        let manager = TokenManager::new();
+
+        assert_eq!(manager.get_token(&provider).await.unwrap(), "token-1");
+        uaa.failing.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        manager.get_token(&provider).await.unwrap();
+
+        // The background refresh at 1.5s fails; the old token is still valid until 2s
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        assert_eq!(uaa.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(manager.get_token(&provider).await.unwrap(), "token-1");
+
+        let status = manager.token_status(&provider);
+        assert!(status.valid);
+        assert!(status.expires_at.is_some());
+        assert_eq!(status.consecutive_failures, 1);
+        assert!(status.last_error.is_some());
+    }
+
+    #[test]
+    fn test_token_key_distinguishes_auth_methods() {
+        let provider = Provider {
+            name: "p".to_string(),
+            uaa_token_url: "https://uaa.example.com/oauth/token".to_string(),
+            uaa_client_id: "client".to_string(),
+            uaa_client_secret: String::new(),
+            certificate: Some("cert-a".to_string()),
+            key: Some("key-a".to_string()),
+            genai_api_url: "https://api.example.com".to_string(),
+            resource_group: "default".to_string(),
+            resource_groups: Vec::new(),
+            weight: 1,
+            enabled: true,
+            http: Default::default(),
+            timeouts: Default::default(),
+        };
+        let rotated = Provider {
+            certificate: Some("cert-b".to_string()),
+            ..provider.clone()
+        };
+
+        assert_ne!(token_key(&provider), token_key(&rotated));
+    }
+}
@@ -0,0 +1,528 @@
+//! Config file validation that reports every problem at once.
+//!
+//! [`validate_file`] runs the static checks; [`check_online`] additionally
+//! fetches tokens and deployments to verify that the configuration works.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::{
+    alias::AliasPattern,
+    client::AiCoreClient,
+    config::{Config, ConfigFile, Model},
+    http,
+    listener::ListenAddress,
+    registry::select_deployments,
+    tls,
+    token::TokenManager,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A single problem found in the configuration
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Where the problem is, e.g. `providers[1].genai_api_url`
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        if self.location.is_empty() {
+            write!(f, "{severity}: {}", self.message)
+        } else {
+            write!(f, "{severity}: {}: {}", self.location, self.message)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub diagnostics: Vec<Diagnostic>,
+    /// The loaded configuration, if it could be loaded
+    pub config: Option<Config>,
+}
+
+impl ValidationReport {
+    fn error(&mut self, location: impl Into<String>, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            location: location.into(),
+            message: message.into(),
+        });
+    }
+
+    fn warning(&mut self, location: impl Into<String>, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            location: location.into(),
+            message: message.into(),
+        });
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.diagnostics.len() - self.error_count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+}
+
+/// Run all static checks on a config file.
+pub fn validate_file(config_file_path: &str) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let document = match Config::read_document(config_file_path) {
+        Ok(document) => document,
+        Err(e) => {
+            report.error("", format!("{e:#}"));
+            return report;
+        }
+    };
+    let file_config = match serde_yaml::from_value::<ConfigFile>(document.clone()) {
+        Ok(file_config) => file_config,
+        Err(e) => {
+            report.error("", format!("Invalid config: {e}"));
+            return report;
+        }
+    };
+
+    for field in file_config.unknown_fields(&document) {
+        if file_config.strict {
+            report.error(field, "unknown field");
+        } else {
+            report.warning(field, "unknown field (ignored)");
+        }
+    }
+
+    for (i, listener) in file_config.listen.iter().enumerate() {
+        if let Err(e) = ListenAddress::parse(&listener.address) {
+            report.error(format!("listen[{i}].address"), format!("{e:#}"));
+        }
+        if let Some(ref tls_config) = listener.tls {
+            if matches!(
+                ListenAddress::parse(&listener.address),
+                Ok(ListenAddress::Unix(_))
+            ) {
+                report.error(
+                    format!("listen[{i}].tls"),
+                    "TLS is only supported on TCP listeners",
+                );
+            } else if let Err(e) = tls::load_server_config(tls_config) {
+                report.error(format!("listen[{i}].tls"), format!("{e:#}"));
+            }
+        }
+    }
+
+    if let Err(e) = http::check(&file_config.http) {
+        report.error("http", format!("{e:#}"));
+    }
+    check_providers(&file_config, &mut report);
+    check_models(&file_config, &mut report);
+    if let Some(ref jwt) = file_config.jwt_auth
+        && let Some(ref url) = jwt.jwks_url
+    {
+        check_url(url, "jwt_auth.jwks_url", &mut report);
+    }
+
+    // Whatever the checks above cannot see (environment, bindings, legacy
+    // credentials) is covered by running the real loader.
+    if !report.has_errors() {
+        match Config::from_file_and_env(file_config) {
+            Ok(config) => report.config = Some(config),
+            Err(e) => report.error("", format!("{e:#}")),
+        }
+    }
+
+    report
+}
+
+fn check_providers(file_config: &ConfigFile, report: &mut ValidationReport) {
+    let mut seen = HashSet::new();
+    for (i, provider) in file_config.providers.iter().enumerate() {
+        let location = format!("providers[{i}]");
+        if !seen.insert(provider.name.as_str()) {
+            report.error(
+                format!("{location}.name"),
+                format!("duplicate provider name '{}'", provider.name),
+            );
+        }
+        if let Some(ref url) = provider.uaa_token_url {
+            check_url(url, &format!("{location}.uaa_token_url"), report);
+        }
+        if let Some(ref url) = provider.genai_api_url {
+            check_url(url, &format!("{location}.genai_api_url"), report);
+        }
+        if provider.http != Default::default()
+            && let Err(e) = http::check(&file_config.http.merged(&provider.http))
+        {
+            report.error(format!("{location}.http"), format!("{e:#}"));
+        }
+        if let Err(e) = provider.clone().resolve() {
+            report.error(location, format!("{e:#}"));
+        }
+    }
+}
+
+fn check_url(value: &str, location: &str, report: &mut ValidationReport) {
+    match reqwest::Url::parse(value) {
+        Ok(url) if !matches!(url.scheme(), "http" | "https") => {
+            report.error(location, format!("'{value}' is not an http(s) URL"))
+        }
+        Ok(url) if url.host_str().is_none_or(str::is_empty) => {
+            report.error(location, format!("'{value}' has no host"))
+        }
+        Ok(_) => {}
+        Err(e) => report.error(location, format!("'{value}' is not a valid URL: {e}")),
+    }
+}
+
+fn check_models(file_config: &ConfigFile, report: &mut ValidationReport) {
+    let models = &file_config.models;
+
+    let mut seen = HashSet::new();
+    for (i, model) in models.iter().enumerate() {
+        if !seen.insert(model.name.as_str()) {
+            report.error(
+                format!("models[{i}].name"),
+                format!("duplicate model name '{}'", model.name),
+            );
+        }
+    }
+
+    let fallbacks = &file_config.fallback_models;
+    for (family, fallback) in [
+        ("claude", &fallbacks.claude),
+        ("openai", &fallbacks.openai),
+        ("gemini", &fallbacks.gemini),
+    ] {
+        if let Some(fallback) = fallback
+            && !models.iter().any(|m| &m.name == fallback)
+        {
+            report.error(
+                format!("fallback_models.{family}"),
+                format!("fallback model '{fallback}' is not configured in models"),
+            );
+        }
+    }
+
+    for (i, model) in models.iter().enumerate() {
+        for (j, alias) in model.aliases.iter().enumerate() {
+            if let Err(e) = AliasPattern::parse(alias) {
+                report.error(format!("models[{i}].aliases[{j}]"), format!("{e:#}"));
+            }
+        }
+    }
+    // Without a providers list the legacy credentials make up provider 'default'
+    let provider_names: Vec<&str> = if file_config.providers.is_empty() {
+        vec!["default"]
+    } else {
+        file_config
+            .providers
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect()
+    };
+    for (i, model) in models.iter().enumerate() {
+        for provider in model.deployment_ids.keys() {
+            if !provider_names.contains(&provider.as_str()) {
+                report.error(
+                    format!("models[{i}].deployment_ids.{provider}"),
+                    format!("unknown provider '{provider}'"),
+                );
+            }
+        }
+    }
+    for (i, rewrite) in file_config.alias_rewrites.iter().enumerate() {
+        if let Err(e) = AliasPattern::parse(&rewrite.pattern) {
+            report.error(format!("alias_rewrites[{i}].pattern"), format!("{e:#}"));
+        }
+    }
+
+    check_alias_shadowing(models, report);
+}
+
+/// Report aliases that can never take effect, or only for some names:
+/// exact model names are matched before aliases, and of equally specific
+/// aliases the one on the alphabetically first model wins.
+fn check_alias_shadowing(models: &[Model], report: &mut ValidationReport) {
+    let mut winner: HashMap<&str, &str> = HashMap::new();
+    for model in models {
+        for alias in &model.aliases {
+            let owner = winner.entry(alias).or_insert(&model.name);
+            if model.name.as_str() < *owner {
+                *owner = &model.name;
+            }
+        }
+    }
+
+    // Distinct aliases that no other check reported, for comparing patterns
+    let mut candidates: Vec<(String, &str, &str, AliasPattern)> = Vec::new();
+    for (i, model) in models.iter().enumerate() {
+        let mut seen = HashSet::new();
+        for (j, alias) in model.aliases.iter().enumerate() {
+            let location = format!("models[{i}].aliases[{j}]");
+            if let Some(owner) = models.iter().find(|m| &m.name == alias) {
+                report.warning(
+                    location,
+                    format!(
+                        "alias '{alias}' is shadowed by the model named '{}'",
+                        owner.name
+                    ),
+                );
+            } else if !seen.insert(alias) {
+                report.warning(location, format!("duplicate alias '{alias}'"));
+            } else if winner[alias.as_str()] != model.name {
+                report.warning(
+                    location,
+                    format!(
+                        "alias '{alias}' is shadowed by the same alias on model '{}'",
+                        winner[alias.as_str()]
+                    ),
+                );
+            } else if let Ok(pattern) = AliasPattern::parse(alias) {
+                candidates.push((location, alias, &model.name, pattern));
+            }
+        }
+    }
+
+    // Different patterns that tie, like `acr models resolve` reports them
+    for (location, alias, model, pattern) in &candidates {
+        let winner = candidates
+            .iter()
+            .filter(|(_, other_alias, other_model, other)| {
+                other_model < model
+                    && other_alias != alias
+                    && pattern.ties_with(other)
+                    && pattern.overlaps(other)
+            })
+            .min_by_key(|(_, _, other_model, _)| *other_model);
+        if let Some((_, other_alias, other_model, _)) = winner {
+            report.warning(
+                location.clone(),
+                format!(
+                    "alias '{alias}' ties with alias '{other_alias}' of model '{other_model}'; names matching both resolve to '{other_model}'"
+                ),
+            );
+        }
+    }
+}
+
+/// Check that every enabled provider issues tokens and that every configured
+/// model has a running deployment on at least one provider, matching its
+/// version, configuration name or pinned deployment id.
+pub async fn check_online(config: &Config, report: &mut ValidationReport) {
+    let token_manager = TokenManager::new();
+    let mut deployed: HashSet<usize> = HashSet::new();
+    let mut all_reachable = true;
+
+    for provider in config.providers.iter().filter(|p| p.enabled) {
+        let location = format!("provider '{}'", provider.name);
+        if let Err(e) = token_manager.get_token(provider).await {
+            report.error(location, format!("failed to fetch token: {e:#}"));
+            all_reachable = false;
+            continue;
+        }
+
+        let client = AiCoreClient::from_provider(provider.clone(), token_manager.clone());
+        match client.build_provider_deployment_mapping().await {
+            Ok(mapping) => {
+                for (group, e) in &mapping.failed_groups {
+                    report.error(
+                        location.clone(),
+                        format!("failed to list deployments in resource group '{group}': {e:#}"),
+                    );
+                    all_reachable = false;
+                }
+                deployed.extend(
+                    config
+                        .models
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, model)| {
+                            !select_deployments(model, &provider.name, &mapping.models).is_empty()
+                        })
+                        .map(|(i, _)| i),
+                );
+            }
+            Err(e) => {
+                report.error(location, format!("failed to list deployments: {e:#}"));
+                all_reachable = false;
+            }
+        }
+    }
+
+    for (i, model) in config.models.iter().enumerate() {
+        if deployed.contains(&i) {
+            continue;
+        }
+        let aicore_model_name = model.aicore_model_name.as_ref().unwrap_or(&model.name);
+        let mut wanted = format!("'{aicore_model_name}'");
+        if let Some(ref version) = model.aicore_model_version {
+            wanted.push_str(&format!(" version '{version}'"));
+        }
+        if let Some(ref configuration_name) = model.configuration_name {
+            wanted.push_str(&format!(" from configuration '{configuration_name}'"));
+        }
+        if !model.deployment_ids.is_empty() {
+            wanted.push_str(" (or its pinned deployment ids)");
+        }
+        let message = format!(
+            "no running deployment of {wanted} found on any provider{}",
+            if all_reachable {
+                ""
+            } else {
+                " (some providers could not be checked)"
+            }
+        );
+        report.error(format!("models[{i}]"), message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn validate_yaml(yaml: &str) -> ValidationReport {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        fs::write(&path, yaml).unwrap();
+        validate_file(path.to_str().unwrap())
+    }
+
+    fn messages(report: &ValidationReport) -> Vec<String> {
+        report.diagnostics.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn test_valid_config() {
+        let report = validate_yaml(
+            r#"
+api_keys: [key]
+providers:
+  - name: eu10
+    uaa_token_url: https://eu10.example.com/oauth/token
+    uaa_client_id: client
+    uaa_client_secret: secret
+    genai_api_url: https://api.eu10.example.com
+models:
+  - name: claude-sonnet-4-5
+    aliases: ["claude-sonnet-*"]
+fallback_models:
+  claude: claude-sonnet-4-5
+"#,
+        );
+
+        assert!(report.diagnostics.is_empty(), "{:?}", messages(&report));
+        assert_eq!(report.config.unwrap().providers.len(), 1);
+    }
+
+    #[test]
+    fn test_reports_all_problems() {
+        let report = validate_yaml(
+            r#"
+api_keys: [key]
+load_balance: fallback
+providers:
+  - name: eu10
+    uaa_token_url: https://eu10.example.com/oauth/token
+    uaa_client_id: client
+    uaa_client_secret: secret
+    genai_api_url: api.eu10.example.com
+  - name: eu10
+    uaa_token_url: https://eu10.example.com/oauth/token
+    uaa_clientid: client
+    uaa_client_secret: secret
+    genai_api_url: https://api.eu10.example.com
+models:
+  - name: gpt-4o
+    aliases: ["gpt-4o-*", "gpt-4o-*"]
+  - name: gpt-4o-mini
+    aliases: ["gpt-4o-*", "gpt-4o"]
+    deployment_ids: {us10: d123}
+fallback_models:
+  openai: gpt-5
+"#,
+        );
+
+        let messages = messages(&report);
+        assert!(report.config.is_none());
+        assert_eq!(report.error_count(), 5, "{messages:#?}");
+        assert_eq!(report.warning_count(), 5, "{messages:#?}");
+        for expected in [
+            "warning: load_balance: unknown field",
+            "warning: providers[1].uaa_clientid: unknown field",
+            "error: providers[0].genai_api_url: 'api.eu10.example.com' is not a valid URL",
+            "error: providers[1].name: duplicate provider name 'eu10'",
+            "error: providers[1]: Provider 'eu10': uaa_client_id is required",
+            "warning: models[0].aliases[1]: duplicate alias 'gpt-4o-*'",
+            "warning: models[1].aliases[0]: alias 'gpt-4o-*' is shadowed by the same alias on model 'gpt-4o'",
+            "warning: models[1].aliases[1]: alias 'gpt-4o' is shadowed by the model named 'gpt-4o'",
+            "error: fallback_models.openai: fallback model 'gpt-5' is not configured",
+            "error: models[1].deployment_ids.us10: unknown provider 'us10'",
+        ] {
+            assert!(
+                messages.iter().any(|m| m.starts_with(expected)),
+                "missing '{expected}' in {messages:#?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_reports_tying_alias_patterns() {
+        let report = validate_yaml(
+            r#"
+api_keys: [key]
+providers:
+  - name: eu10
+    uaa_token_url: https://eu10.example.com/oauth/token
+    uaa_client_id: client
+    uaa_client_secret: secret
+    genai_api_url: https://api.eu10.example.com
+models:
+  - name: claude-sonnet-4-5
+    aliases: ["claude-*-4"]
+  - name: claude-opus-4-5
+    aliases: ["claude-4-*", "claude-opus-*"]
+  - name: gpt-4o
+    aliases: ["gpt-*-4"]
+"#,
+        );
+
+        let messages = messages(&report);
+        assert_eq!(report.warning_count(), 1, "{messages:#?}");
+        assert!(
+            messages[0].starts_with(
+                "warning: models[0].aliases[0]: alias 'claude-*-4' ties with alias 'claude-4-*' of model 'claude-opus-4-5'"
+            ),
+            "{messages:#?}"
+        );
+    }
+
+    #[test]
+    fn test_unreadable_config() {
+        let report = validate_yaml("providers: [");
+        assert_eq!(report.error_count(), 1);
+        assert!(report.config.is_none());
+    }
+}